@@ -9,11 +9,16 @@
 //! new memory settings.
 //!
 //! The build script also sets the linker flags to tell it which link script to use.
+//!
+//! Finally, the build script generates tables describing the data fields of every EnOcean
+//! Equipment Profile defined in `eep/eep.xml` that it knows how to interpret. These tables are
+//! interpreted by the `generic_eep` module.
 
 use std::env;
+use std::fmt::Write as _;
 use std::fs::File;
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 fn main() {
     // Put `memory.x` in our output directory and ensure it's
@@ -40,4 +45,180 @@ fn main() {
 
     // Set the linker script to the one provided by cortex-m-rt.
     println!("cargo:rustc-link-arg=-Tlink.x");
+
+    // Generate the EEP tables.
+    println!("cargo:rerun-if-changed=eep/eep.xml");
+    generate_eep_tables(out);
+}
+
+
+/// A data field of an EEP that we know how to interpret.
+struct Field {
+    quantity: &'static str,
+    bit_offset: u32,
+    bit_size: u32,
+    range: (i64, i64),
+    scale_milli: (i64, i64),
+}
+
+
+/// Parses a number as it appears in the EEP XML (decimal or `0x`-prefixed hexadecimal).
+fn parse_integer(text: &str) -> i64 {
+    let trimmed = text.trim().trim_start_matches('+');
+    let (negative, unsigned) = match trimmed.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, trimmed),
+    };
+    let value = match unsigned.strip_prefix("0x").or_else(|| unsigned.strip_prefix("0X")) {
+        Some(hex) => i64::from_str_radix(hex, 16),
+        None => unsigned.parse(),
+    }.unwrap_or_else(|_| panic!("invalid number {:?} in EEP XML", text));
+    if negative { -value } else { value }
+}
+
+/// Parses a decimal number as it appears in the EEP XML into thousandths.
+fn parse_milli(text: &str) -> i64 {
+    let trimmed = text.trim().trim_start_matches('+');
+    let (negative, unsigned) = match trimmed.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, trimmed),
+    };
+    let (integer_text, fraction_text) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+    assert!(fraction_text.len() <= 3, "too many decimal places in {:?} in EEP XML", text);
+    let integer: i64 = integer_text.parse()
+        .unwrap_or_else(|_| panic!("invalid number {:?} in EEP XML", text));
+    let fraction: i64 = format!("{:0<3}", fraction_text).parse()
+        .unwrap_or_else(|_| panic!("invalid number {:?} in EEP XML", text));
+    let value = integer * 1000 + fraction;
+    if negative { -value } else { value }
+}
+
+/// Returns the text of the child element with the given name.
+fn child_text<'a>(node: roxmltree::Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.children()
+        .find(|c| c.has_tag_name(name))
+        .and_then(|c| c.text())
+}
+
+/// Returns the minimum and maximum of the child element with the given name (`range` or `scale`).
+fn child_min_max<'a>(node: roxmltree::Node<'a, '_>, name: &str) -> Option<(&'a str, &'a str)> {
+    let child = node.children().find(|c| c.has_tag_name(name))?;
+    Some((child_text(child, "min")?, child_text(child, "max")?))
+}
+
+/// Collects the data fields of a profile case that we know how to interpret.
+///
+/// Returns the number of data bits and the fields, or `None` if the case contains no measurement.
+/// Panics if a field is not in the unit that `generic_eep` expects for its quantity.
+fn interpret_case(case: roxmltree::Node, name: &str) -> Option<(u32, Vec<Field>)> {
+    let mut fields = Vec::new();
+    let mut data_bits = 0;
+    for datafield in case.children().filter(|c| c.has_tag_name("datafield")) {
+        let bit_offset = parse_integer(child_text(datafield, "bitoffs").expect("bitoffs missing")) as u32;
+        let bit_size = parse_integer(child_text(datafield, "bitsize").expect("bitsize missing")) as u32;
+        data_bits = data_bits.max(bit_offset + bit_size);
+
+        let (quantity, expected_unit) = match child_text(datafield, "shortcut") {
+            Some("TMP") => ("Temperature", "°C"),
+            Some("HUM") => ("RelativeHumidity", "%"),
+            Some("ILL") => ("Illumination", "lx"),
+            Some("Conc") => ("Co2", "ppm"),
+            Some("LRNB") => ("LearnBit", ""),
+            _ => continue, // not (yet) interesting to us
+        };
+        let unit = child_text(datafield, "unit").unwrap_or("").trim();
+        assert_eq!(
+            unit, expected_unit,
+            "{} field of profile {} has unexpected unit", quantity, name,
+        );
+
+        let (range, scale_milli) = if quantity == "LearnBit" {
+            ((0, 1), (0, 1000))
+        } else {
+            let (range_min, range_max) = child_min_max(datafield, "range")
+                .unwrap_or_else(|| panic!("field of profile {} has no range", name));
+            let (scale_min, scale_max) = child_min_max(datafield, "scale")
+                .unwrap_or_else(|| panic!("field of profile {} has no scale", name));
+            (
+                (parse_integer(range_min), parse_integer(range_max)),
+                (parse_milli(scale_min), parse_milli(scale_max)),
+            )
+        };
+        fields.push(Field {
+            quantity,
+            bit_offset,
+            bit_size,
+            range,
+            scale_milli,
+        });
+    }
+
+    if fields.iter().all(|f| f.quantity == "LearnBit") {
+        // nothing to measure
+        return None;
+    }
+    Some((data_bits, fields))
+}
+
+fn generate_eep_tables(out: &Path) {
+    let xml = std::fs::read_to_string("eep/eep.xml")
+        .expect("failed to read eep/eep.xml");
+    let document = roxmltree::Document::parse(&xml)
+        .expect("failed to parse eep/eep.xml");
+
+    let mut profiles = Vec::new();
+    for rorg_node in document.descendants().filter(|n| n.has_tag_name("rorg")) {
+        let rorg = parse_integer(child_text(rorg_node, "number").expect("rorg number missing"));
+        for func_node in rorg_node.children().filter(|c| c.has_tag_name("func")) {
+            let func = parse_integer(child_text(func_node, "number").expect("func number missing"));
+            for type_node in func_node.children().filter(|c| c.has_tag_name("type")) {
+                let type_ = parse_integer(child_text(type_node, "number").expect("type number missing"));
+                let name = format!("{:02X}-{:02X}-{:02X}", rorg, func, type_);
+
+                let cases: Vec<_> = type_node.children()
+                    .filter(|c| c.has_tag_name("case"))
+                    .collect();
+                if cases.len() != 1 {
+                    // profiles with multiple cases are not supported
+                    continue;
+                }
+
+                let Some((data_bits, fields)) = interpret_case(cases[0], &name) else {
+                    continue;
+                };
+                let profile = ((rorg as u32) << 16) | ((func as u32) << 8) | (type_ as u32);
+                profiles.push((profile, data_bits.div_ceil(8), fields));
+            }
+        }
+    }
+
+    // allow binary search
+    profiles.sort_by_key(|(profile, _, _)| *profile);
+    profiles.dedup_by_key(|(profile, _, _)| *profile);
+
+    let mut code = String::new();
+    writeln!(code, "// generated by build.rs from eep/eep.xml; do not edit").unwrap();
+    writeln!(code, "pub const PROFILES: &[ProfileDefinition] = &[").unwrap();
+    for (profile, data_length, fields) in &profiles {
+        writeln!(code, "    ProfileDefinition {{").unwrap();
+        writeln!(code, "        profile: 0x{:06X},", profile).unwrap();
+        writeln!(code, "        data_length: {},", data_length).unwrap();
+        writeln!(code, "        fields: &[").unwrap();
+        for field in fields {
+            writeln!(
+                code,
+                "            FieldDefinition {{ quantity: Quantity::{}, bit_offset: {}, bit_size: {}, range: ({}, {}), scale_milli: ({}, {}) }},",
+                field.quantity, field.bit_offset, field.bit_size,
+                field.range.0, field.range.1, field.scale_milli.0, field.scale_milli.1,
+            ).unwrap();
+        }
+        writeln!(code, "        ],").unwrap();
+        writeln!(code, "    }},").unwrap();
+    }
+    writeln!(code, "];").unwrap();
+
+    File::create(out.join("eep_tables.rs"))
+        .unwrap()
+        .write_all(code.as_bytes())
+        .unwrap();
 }
@@ -8,64 +8,298 @@ use stm32f7::stm32f745::Peripherals;
 use crate::i2c::{I2c, I2cAddress};
 
 
+/// The readings above which the sensor is switched to a less sensitive range.
+const HIGH_COUNTS: u16 = 58_000;
+
+/// The readings below which the sensor is switched to a more sensitive range.
+///
+/// Successive ranges differ by a factor of at most 4, so the reading after switching is still
+/// below [`HIGH_COUNTS`].
+const LOW_COUNTS: u16 = 10_000;
+
+/// How far (in percent) the reading may move before the sensor raises an interrupt.
+const WINDOW_PERCENT: u16 = 5;
+
+/// How often the sensor is read even if it has not raised an interrupt, in milliseconds, in case an
+/// interrupt has been missed.
+const RECHECK_MILLIS: u32 = 30_000;
+
+
+/// How long the sensor integrates the light for each measurement.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum IntegrationTime {
+    Ms3_125 = 0b000,
+    Ms6_25 = 0b001,
+    Ms12_5 = 0b010,
+    Ms25 = 0b011,
+    Ms50 = 0b100,
+    Ms100 = 0b101,
+    Ms200 = 0b110,
+    Ms400 = 0b111,
+}
+impl IntegrationTime {
+    pub const fn as_micros(&self) -> u32 {
+        3_125 << (*self as u8)
+    }
+}
+
+
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Gain {
+    Single = 0b00,
+    Double = 0b01,
+    #[allow(unused)]
+    TwoThirds = 0b10,
+    Half = 0b11,
+}
+
+
+/// The combination of settings that determines how sensitive the sensor is.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Range {
+    pub integration_time: IntegrationTime,
+    pub gain: Gain,
+
+    /// Whether only a quarter of the photodiode is used.
+    pub quarter_photodiode: bool,
+}
+impl Range {
+    const fn new(integration_time: IntegrationTime, gain: Gain, quarter_photodiode: bool) -> Self {
+        Self { integration_time, gain, quarter_photodiode }
+    }
+
+    /// Returns how much light one count corresponds to, in units of 0.000_001 lux.
+    pub const fn resolution_microlux(&self) -> u32 {
+        // from the resolution table in the datasheet (400 ms; whole photodiode, quarter photodiode)
+        let (whole, quarter) = match self.gain {
+            Gain::Double => (3_400, 13_600),
+            Gain::Single => (6_800, 27_200),
+            Gain::TwoThirds => (10_300, 41_200),
+            Gain::Half => (13_600, 54_400),
+        };
+        let at_400_ms = if self.quarter_photodiode { quarter } else { whole };
+
+        // halving the integration time doubles the lux per count
+        at_400_ms << (IntegrationTime::Ms400 as u8 - self.integration_time as u8)
+    }
+}
+
+
+/// The ranges between which the sensor switches, from the most to the least sensitive.
+///
+/// Shorter integration times are preferred to lower gains so that the readings follow changes in
+/// brightness quickly.
+const RANGES: [Range; 11] = [
+    Range::new(IntegrationTime::Ms400, Gain::Double, false), // 0.0034 lx/count
+    Range::new(IntegrationTime::Ms200, Gain::Double, false), // 0.0068 lx/count
+    Range::new(IntegrationTime::Ms100, Gain::Double, false), // 0.0136 lx/count
+    Range::new(IntegrationTime::Ms50, Gain::Double, false), // 0.0272 lx/count
+    Range::new(IntegrationTime::Ms25, Gain::Double, false), // 0.0544 lx/count
+    Range::new(IntegrationTime::Ms25, Gain::Single, false), // 0.1088 lx/count
+    Range::new(IntegrationTime::Ms25, Gain::Half, false), // 0.2176 lx/count
+    Range::new(IntegrationTime::Ms25, Gain::Half, true), // 0.8704 lx/count
+    Range::new(IntegrationTime::Ms12_5, Gain::Half, true), // 1.7408 lx/count
+    Range::new(IntegrationTime::Ms6_25, Gain::Half, true), // 3.4816 lx/count
+    Range::new(IntegrationTime::Ms3_125, Gain::Half, true), // 6.9632 lx/count
+];
+
+/// The range in which the sensor starts out (the same as the fixed range of earlier versions).
+const INITIAL_RANGE_INDEX: usize = 5;
+
+
 #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct AmbientLightSensor {
     pub i2c_address: I2cAddress,
 }
 impl AmbientLightSensor {
     pub fn set_up<I: I2c>(&self, peripherals: &Peripherals) {
-        I::write_data(
+        self.set_range::<I>(peripherals, RANGES[INITIAL_RANGE_INDEX]);
+        // a window of 0 to 0 raises an interrupt with the first measurement
+        self.set_thresholds::<I>(peripherals, 0, 0);
+    }
+
+    pub fn read_ambient_light<I: I2c>(&self, peripherals: &Peripherals) -> u16 {
+        let write_buf = [
+            0x10, // ALS_DATA_L (followed by ALS_DATA_H, increments automatically)
+        ];
+        let mut read_buf = [0u8; 2];
+
+        // reading from the VEML4031X00 only works when, after writing the register number,
+        // we issue a repeated start condition and perform the read;
+        // if we relinquish the bus between writing and reading, the VEML4031X00 forgets the
+        // register number and returns garbage
+        I::write_then_read_data(
             peripherals,
             self.i2c_address,
-            &[
-                0x00, // ALS_CONF_0 (followed by ALS_CONF_1, increments automatically)
-                (
-                    (0b0 << 7) // reserved
-                    | (0b011 << 4) // 25ms integration time
-                    | (0b0 << 3) // continuous measurement
-                    | (0b0 << 2) // no measurement trigger
-                    | (0b0 << 1) // no interrupt
-                    | (0b0 << 0) // turn on the sensor (1/2)
-                ),
-                (
-                    (0b0 << 7) // turn on the sensor (2/2)
-                    | (0b0 << 6) // use whole photodiode
-                    | (0b0 << 5) // reserved
-                    | (0b00 << 3) // x1 gain
-                    | (0b11 << 1) // interrupt hysteresis 8
-                    | (0b0 << 0) // don't run the calibration algorithm now
-                ),
-            ],
+            &write_buf,
+            &mut read_buf,
         );
+        u16::from_le_bytes(read_buf)
+    }
+
+    /// Sets the window outside of which the sensor raises an interrupt, in counts.
+    pub fn set_thresholds<I: I2c>(&self, peripherals: &Peripherals, low: u16, high: u16) {
+        let [low_l, low_h] = low.to_le_bytes();
+        let [high_l, high_h] = high.to_le_bytes();
         I::write_data(
             peripherals,
             self.i2c_address,
             &[
                 0x04, // ALS_THDH_L (followed by _H, _THDL_L, _H; increments automatically)
-                0x00, // high threshold, low byte
-                0x00, // high threshold, high byte
-                0x00, // low threshold, low byte
-                0x00, // low threshold, high byte
+                high_l, // high threshold, low byte
+                high_h, // high threshold, high byte
+                low_l, // low threshold, low byte
+                low_h, // low threshold, high byte
             ],
         );
     }
 
-    pub fn read_ambient_light<I: I2c>(&self, peripherals: &Peripherals) -> u16 {
+    /// Reads and clears the interrupt flags, which releases the interrupt line.
+    pub fn read_interrupt_flags<I: I2c>(&self, peripherals: &Peripherals) -> u8 {
         let write_buf = [
-            0x10, // ALS_DATA_L (followed by ALS_DATA_H, increments automatically)
+            0x17, // ALS_INT
         ];
-        let mut read_buf = [0u8; 2];
+        let mut read_buf = [0u8; 1];
 
-        // reading from the VEML4031X00 only works when, after writing the register number,
-        // we issue a repeated start condition and perform the read;
-        // if we relinquish the bus between writing and reading, the VEML4031X00 forgets the
-        // register number and returns garbage
+        // repeated start condition required, see read_ambient_light
         I::write_then_read_data(
             peripherals,
             self.i2c_address,
             &write_buf,
             &mut read_buf,
         );
-        u16::from_le_bytes(read_buf)
+        read_buf[0]
+    }
+
+    pub fn set_range<I: I2c>(&self, peripherals: &Peripherals, range: Range) {
+        let integration_time = range.integration_time as u8;
+        let gain = range.gain as u8;
+        let quarter_photodiode: u8 = if range.quarter_photodiode { 1 } else { 0 };
+        I::write_data(
+            peripherals,
+            self.i2c_address,
+            &[
+                0x00, // ALS_CONF_0 (followed by ALS_CONF_1, increments automatically)
+                (
+                    (0b0 << 7) // reserved
+                    | (integration_time << 4) // integration time
+                    | (0b0 << 3) // continuous measurement
+                    | (0b0 << 2) // no measurement trigger
+                    | (0b1 << 1) // raise an interrupt when leaving the threshold window
+                    | (0b0 << 0) // turn on the sensor (1/2)
+                ),
+                (
+                    (0b0 << 7) // turn on the sensor (2/2)
+                    | (quarter_photodiode << 6) // whole or quarter photodiode
+                    | (0b0 << 5) // reserved
+                    | (gain << 3) // gain
+                    | (0b01 << 1) // interrupt hysteresis 2
+                    | (0b0 << 0) // don't run the calibration algorithm now
+                ),
+            ],
+        );
+    }
+}
+
+
+/// Switches the ambient light sensor between ranges to keep its readings accurate, and converts
+/// them to lux.
+///
+/// The sensor is only read when it raises an interrupt because the reading has left a window
+/// around the previous one; the window is then moved to the new reading.
+pub struct AutoRangingAmbientLightSensor {
+    sensor: AmbientLightSensor,
+    range_index: usize,
+
+    /// The value of the SysTick counter at which the range was last changed.
+    range_changed_at: u32,
+
+    /// The value of the SysTick counter at which the sensor was last read.
+    read_at: u32,
+
+    /// Whether the sensor has to be read as soon as its measurement is valid.
+    read_pending: bool,
+
+    /// The most recent valid reading, in units of 0.001 lux.
+    millilux: u32,
+}
+impl AutoRangingAmbientLightSensor {
+    /// Wraps a sensor that has been set up; `counter` is the current value of the SysTick counter.
+    pub const fn new(sensor: AmbientLightSensor, counter: u32) -> Self {
+        Self {
+            sensor,
+            range_index: INITIAL_RANGE_INDEX,
+            range_changed_at: counter,
+            read_at: counter,
+            read_pending: true,
+            millilux: 0,
+        }
+    }
+
+    /// Returns the ambient light, in units of 0.001 lux; `interrupted` is whether the sensor has
+    /// raised an interrupt since the last call and `counter` is the current value of the SysTick
+    /// counter.
+    ///
+    /// The sensor is only read if it has raised an interrupt (or has not been read for a while),
+    /// switching to a different range if necessary. While a measurement in a new range is pending,
+    /// or if the reading has to be discarded because it is out of range, the previous value is
+    /// returned.
+    pub fn read_millilux<I: I2c>(&mut self, peripherals: &Peripherals, interrupted: bool, counter: u32) -> u32 {
+        if interrupted || counter.wrapping_sub(self.read_at) >= RECHECK_MILLIS {
+            self.read_pending = true;
+        }
+        if !self.read_pending {
+            return self.millilux;
+        }
+
+        // the measurement running at the time of the change still uses the old range,
+        // so wait for the measurement after that
+        let range = RANGES[self.range_index];
+        let settle_millis = 2 * range.integration_time.as_micros().div_ceil(1000);
+        if counter.wrapping_sub(self.range_changed_at) <= settle_millis {
+            return self.millilux;
+        }
+        self.read_pending = false;
+        self.read_at = counter;
+
+        // release the interrupt line so that the next interrupt causes an edge
+        self.sensor.read_interrupt_flags::<I>(peripherals);
+        let counts = self.sensor.read_ambient_light::<I>(peripherals);
+
+        let can_decrease = self.range_index + 1 < RANGES.len();
+        let can_increase = self.range_index > 0;
+        let new_range_index = if counts > HIGH_COUNTS && can_decrease {
+            self.range_index + 1
+        } else if counts < LOW_COUNTS && can_increase {
+            self.range_index - 1
+        } else {
+            self.range_index
+        };
+
+        if counts <= HIGH_COUNTS || new_range_index == self.range_index {
+            let microlux = u64::from(counts) * u64::from(range.resolution_microlux());
+            self.millilux = u32::try_from(microlux / 1000).unwrap_or(u32::MAX);
+        }
+        if new_range_index != self.range_index {
+            // read again once the new range has settled
+            self.range_index = new_range_index;
+            self.range_changed_at = counter;
+            self.read_pending = true;
+            self.sensor.set_range::<I>(peripherals, RANGES[new_range_index]);
+        } else {
+            // raise an interrupt once the reading leaves the window around the current one
+            // or the range has to be changed
+            let margin = (u32::from(counts) * u32::from(WINDOW_PERCENT) / 100).max(1) as u16;
+            let mut low = counts.saturating_sub(margin);
+            let mut high = counts.saturating_add(margin);
+            if can_decrease {
+                high = high.min(HIGH_COUNTS);
+            }
+            if can_increase {
+                low = low.max(LOW_COUNTS);
+            }
+            self.sensor.set_thresholds::<I>(peripherals, low, high);
+        }
+        self.millilux
     }
 }
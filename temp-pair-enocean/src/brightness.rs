@@ -0,0 +1,196 @@
+//! Mapping the ambient light to the brightness of the displays.
+//!
+//! The ambient light is first mapped onto a brightness by a gamma curve between a minimum and a
+//! maximum. The result is then smoothed by a low-pass filter, and the brightness of the displays
+//! only follows it once it has moved far enough, so that light hovering around a step does not
+//! make the displays flicker.
+
+
+/// The highest brightness the displays support.
+pub const MAX_LEVEL: u16 = 0x0FFF;
+
+/// `2^(2^-k)` for k from 1 to 16, in units of 2^-30.
+const EXP2_FRACTIONS: [u64; 16] = [
+    1518500250, 1276901417, 1170923762, 1121280436,
+    1097253708, 1085434106, 1079572136, 1076653033,
+    1075196443, 1074468888, 1074105294, 1073923544,
+    1073832680, 1073787251, 1073764537, 1073753181,
+];
+
+
+/// Returns the base-2 logarithm of a positive value, in units of 2^-16.
+fn log2_q16(value: u32) -> i32 {
+    debug_assert!(value > 0);
+    let integer = 31 - value.leading_zeros();
+
+    // normalize into [1, 2) with 30 fractional bits, then square repeatedly;
+    // each time the square reaches 2, the next bit of the fraction is 1
+    let mut mantissa = (u64::from(value) << 30) >> integer;
+    let mut fraction = 0i32;
+    for bit in (0..16).rev() {
+        mantissa = (mantissa * mantissa) >> 30;
+        if mantissa >= 2 << 30 {
+            mantissa >>= 1;
+            fraction |= 1 << bit;
+        }
+    }
+    ((integer as i32) << 16) | fraction
+}
+
+/// Returns 2 to the power of a value of at most 0 given in units of 2^-16, in units of 2^-16.
+fn exp2_q16(exponent: i32) -> u32 {
+    debug_assert!(exponent <= 0);
+
+    // split into a negative integer and a fraction in [0, 1)
+    let shift = (-exponent + 0xFFFF) >> 16;
+    let fraction = exponent + (shift << 16);
+
+    let mut result: u64 = 1 << 30;
+    for (k, factor) in EXP2_FRACTIONS.iter().enumerate() {
+        if fraction & (1 << (15 - k)) != 0 {
+            result = (result * factor) >> 30;
+        }
+    }
+    (result >> 14 >> shift.min(63)) as u32
+}
+
+
+/// How the ambient light is mapped onto the brightness of the displays.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct BrightnessCurve {
+    /// The ambient light at and above which the displays are at their maximum brightness, in lux.
+    pub full_lux: u16,
+
+    /// The exponent of the curve, in units of 0.1; the brightness follows the ambient light raised
+    /// to the power of 1/gamma, so a higher gamma brightens the displays faster in dim light.
+    pub gamma_tenths: u8,
+
+    /// The brightness in complete darkness.
+    pub min_level: u16,
+
+    /// The brightness at `full_lux` and above.
+    pub max_level: u16,
+}
+impl BrightnessCurve {
+    /// Returns the brightness for the given ambient light, given in units of 0.001 lux.
+    pub fn level(&self, millilux: u32) -> u16 {
+        let max_level = self.max_level.min(MAX_LEVEL);
+        let min_level = self.min_level.min(max_level);
+        let full_millilux = u32::from(self.full_lux.max(1)) * 1000;
+        if millilux >= full_millilux {
+            return max_level;
+        }
+        if millilux == 0 {
+            return min_level;
+        }
+
+        // (millilux / full_millilux)^(1/gamma) = 2^((log2(millilux) - log2(full_millilux)) / gamma)
+        let log2_ratio = log2_q16(millilux) - log2_q16(full_millilux);
+        let gamma_tenths = i32::from(self.gamma_tenths.max(1));
+        let fraction = exp2_q16(log2_ratio * 10 / gamma_tenths);
+
+        let span = u32::from(max_level - min_level);
+        min_level + ((span * fraction + (1 << 15)) >> 16) as u16
+    }
+}
+
+
+/// Smooths the brightness over time.
+pub struct BrightnessFilter {
+    /// The filtered brightness, in units of 1/256.
+    filtered: u32,
+
+    /// The brightness currently shown.
+    level: u16,
+
+    /// The value of the SysTick counter at which the filtered brightness was last moved (or found
+    /// to be at the target).
+    updated_at: u32,
+}
+impl BrightnessFilter {
+    /// Creates a filter that starts out at the given brightness; `counter` is the current value of
+    /// the SysTick counter.
+    pub const fn new(level: u16, counter: u32) -> Self {
+        Self {
+            filtered: (level as u32) << 8,
+            level,
+            updated_at: counter,
+        }
+    }
+
+    /// Moves the filtered brightness towards `target` and returns the brightness to show.
+    ///
+    /// `time_constant_millis` is the time it takes the filtered brightness to cover about two
+    /// thirds of the distance to the target (0 to follow it immediately). The brightness shown only
+    /// changes once the filtered brightness differs from it by more than `hysteresis_percent`
+    /// percent (but at least by one step, unless `hysteresis_percent` is 0).
+    pub fn update(&mut self, target: u16, time_constant_millis: u32, hysteresis_percent: u8, counter: u32) -> u16 {
+        let elapsed = counter.wrapping_sub(self.updated_at);
+
+        let target_filtered = u32::from(target) << 8;
+        if time_constant_millis == 0 {
+            self.filtered = target_filtered;
+            self.updated_at = counter;
+        } else {
+            // rounded to the nearest 1/256, so that the last 1/256 is covered too
+            let difference = i64::from(target_filtered) - i64::from(self.filtered);
+            let denominator = i64::from(time_constant_millis) + i64::from(elapsed);
+            let step = (difference * i64::from(elapsed) + difference.signum() * denominator / 2) / denominator;
+
+            // with frequent updates, the step may round to 0; keep the time that has passed
+            // until it adds up to a step instead of losing it
+            if step != 0 || difference == 0 {
+                self.filtered = (i64::from(self.filtered) + step) as u32;
+                self.updated_at = counter;
+            }
+        }
+
+        let filtered_level = ((self.filtered + (1 << 7)) >> 8) as u16;
+        let min_threshold = if hysteresis_percent > 0 { 1 } else { 0 };
+        let threshold = (u32::from(self.level) * u32::from(hysteresis_percent) / 100).max(min_threshold);
+        if u32::from(filtered_level.abs_diff(self.level)) > threshold {
+            self.level = filtered_level;
+        }
+        self.level
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::BrightnessFilter;
+
+    #[test]
+    fn test_filter_converges_with_frequent_updates() {
+        for (start, target) in [(0, 4095), (4095, 0), (100, 110), (110, 100), (1000, 1001)] {
+            let mut filter = BrightnessFilter::new(start, 0);
+            let mut level = start;
+            // a minute in steps of 1 ms with a time constant of 5 s
+            for counter in 1..=60_000 {
+                level = filter.update(target, 5000, 0, counter);
+            }
+            assert_eq!(level, target, "from {} to {}", start, target);
+            assert_eq!(filter.filtered, u32::from(target) << 8, "from {} to {}", start, target);
+        }
+    }
+
+    #[test]
+    fn test_filter_ignores_repeated_counter() {
+        let mut filter = BrightnessFilter::new(0, 0);
+        for _ in 0..1000 {
+            filter.update(4095, 5000, 0, 0);
+        }
+        assert_eq!(filter.filtered, 0);
+
+        // the time that has passed is not lost
+        let level = filter.update(4095, 5000, 0, 5000);
+        assert_eq!(level, 2048);
+    }
+
+    #[test]
+    fn test_filter_hysteresis() {
+        let mut filter = BrightnessFilter::new(1000, 0);
+        assert_eq!(filter.update(1040, 0, 5, 1), 1000);
+        assert_eq!(filter.update(1060, 0, 5, 2), 1060);
+    }
+}
@@ -0,0 +1,114 @@
+//! A line-based text console on the emergency UART.
+
+
+use core::fmt;
+
+use stm32f7::stm32f745::Peripherals;
+
+use crate::uart::{Uart, Usart3};
+
+
+type ConsoleUart = Usart3;
+
+const LINE_LENGTH: usize = 64;
+
+
+/// Writes text to the console.
+pub struct ConsoleWriter<'a> {
+    peripherals: &'a Peripherals,
+}
+impl<'a> ConsoleWriter<'a> {
+    pub fn new(peripherals: &'a Peripherals) -> Self {
+        Self { peripherals }
+    }
+}
+impl<'a> fmt::Write for ConsoleWriter<'a> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        ConsoleUart::write(self.peripherals, s.as_bytes());
+        Ok(())
+    }
+}
+
+
+/// Collects incoming bytes into lines.
+pub struct Console {
+    line: [u8; LINE_LENGTH],
+    line_length: usize,
+    overflowed: bool,
+}
+impl Console {
+    pub const fn new() -> Self {
+        Self {
+            line: [0u8; LINE_LENGTH],
+            line_length: 0,
+            overflowed: false,
+        }
+    }
+
+    /// Processes the bytes received so far and returns the next complete line, if any.
+    ///
+    /// Received characters are echoed back. Lines that are too long are dropped in their entirety.
+    pub fn poll_line(&mut self, peripherals: &Peripherals) -> Option<&str> {
+        while let Some(b) = ConsoleUart::take_byte() {
+            match b {
+                b'\r'|b'\n' => {
+                    ConsoleUart::write(peripherals, b"\r\n");
+                    let length = self.line_length;
+                    let overflowed = self.overflowed;
+                    self.line_length = 0;
+                    self.overflowed = false;
+                    if overflowed || length == 0 {
+                        continue;
+                    }
+                    return core::str::from_utf8(&self.line[..length]).ok();
+                },
+                0x08|0x7F => {
+                    // backspace
+                    if self.line_length > 0 {
+                        self.line_length -= 1;
+                        ConsoleUart::write(peripherals, b"\x08 \x08");
+                    }
+                },
+                _ => {
+                    if self.line_length < LINE_LENGTH {
+                        self.line[self.line_length] = b;
+                        self.line_length += 1;
+                        ConsoleUart::write(peripherals, &[b]);
+                    } else {
+                        self.overflowed = true;
+                    }
+                },
+            }
+        }
+        None
+    }
+}
+
+
+/// Parses a decimal number or a hexadecimal number prefixed with `0x`, either with an optional
+/// leading minus sign.
+pub fn parse_i64(text: &str) -> Option<i64> {
+    let (negative, unsigned_text) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+    let (radix, digits) = match unsigned_text.strip_prefix("0x") {
+        Some(rest) => (16, rest),
+        None => (10, unsigned_text),
+    };
+    if digits.is_empty() {
+        return None;
+    }
+
+    let mut value: i64 = 0;
+    for c in digits.chars() {
+        let digit = c.to_digit(radix)?;
+        value = value.checked_mul(radix.into())?
+            .checked_add(digit.into())?;
+    }
+    if negative {
+        Some(-value)
+    } else {
+        Some(value)
+    }
+}
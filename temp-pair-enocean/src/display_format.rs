@@ -0,0 +1,86 @@
+//! Formatting of values for a three-digit seven-segment display.
+//!
+//! The functions in this module accept any value; values that do not fit on the display are
+//! replaced by a status word.
+
+
+/// The characters shown on a three-digit display and whether their decimal points are lit.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct DisplayText {
+    pub characters: [u8; 3],
+    pub decimal_points: [bool; 3],
+}
+impl DisplayText {
+    /// Nothing is known.
+    pub const BLANK: Self = Self::new(*b"   ");
+
+    /// The value is too large to be shown.
+    pub const HIGH: Self = Self::new(*b" HI");
+
+    /// The value is too small to be shown.
+    pub const LOW: Self = Self::new(*b" LO");
+
+    /// The sensor sends telegrams that do not match its configured profile.
+    pub const ERROR: Self = Self::new(*b"Err");
+
+    /// The configured profile of the sensor cannot be decoded.
+    pub const FORMAT_UNKNOWN: Self = Self::new(*b"-F-");
+
+    pub const fn new(characters: [u8; 3]) -> Self {
+        Self {
+            characters,
+            decimal_points: [false; 3],
+        }
+    }
+
+    /// Formats a temperature given in tenths of a degree.
+    ///
+    /// Between -10 and 100 degrees, one decimal is shown; beyond that, whole degrees.
+    pub fn temperature_tenths(temperature_tenths: i32) -> Self {
+        let digit = |value: i32| b'0' + u8::try_from(value % 10).unwrap();
+
+        if temperature_tenths <= -1000 {
+            // t <= -100.0°
+            Self::LOW
+        } else if temperature_tenths <= -100 {
+            // -100.0° < t <= -10.0°
+            // show as -TT
+            let abs_temp = (-temperature_tenths) / 10;
+            Self::new([b'-', digit(abs_temp / 10), digit(abs_temp)])
+        } else if temperature_tenths < 0 {
+            // -10.0° < t < 0.0°
+            // show as -T.T
+            let abs_temp = -temperature_tenths;
+            Self {
+                characters: [b'-', digit(abs_temp / 10), digit(abs_temp)],
+                decimal_points: [false, true, false],
+            }
+        } else if temperature_tenths < 100 {
+            // 0.0° <= t < 10.0°
+            // show as T.T
+            Self {
+                characters: [b' ', digit(temperature_tenths / 10), digit(temperature_tenths)],
+                decimal_points: [false, true, false],
+            }
+        } else if temperature_tenths < 1000 {
+            // 10.0° <= t < 100.0°
+            // show as TT.T
+            Self {
+                characters: [
+                    digit(temperature_tenths / 100),
+                    digit(temperature_tenths / 10),
+                    digit(temperature_tenths),
+                ],
+                decimal_points: [false, true, false],
+            }
+        } else if temperature_tenths < 9995 {
+            // 100.0° <= t < 999.5°
+            // show as TTT
+            let rounded_temp = (temperature_tenths + 5) / 10;
+            Self::new([digit(rounded_temp / 100), digit(rounded_temp / 10), digit(rounded_temp)])
+        } else {
+            // t >= 999.5°
+            Self::HIGH
+        }
+    }
+}
@@ -0,0 +1,972 @@
+//! Decoding of EnOcean Equipment Profiles (EEPs).
+//!
+//! An EEP is identified by three bytes: RORG (the telegram type), FUNC and TYPE. Throughout this
+//! firmware, it is stored as a `u32` in the form `0x00RRFFTT`.
+
+
+use core::fmt;
+
+use crate::wall_clock::{Date, TimeOfDay};
+
+
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum AccelerationStatus {
+    PeriodicUpdate,
+    Threshold1Exceeded,
+    Threshold2Exceeded,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum ContactState {
+    Open,
+    Closed,
+}
+
+/// The state of a window, ordered from least to most open.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum WindowState {
+    Closed,
+    Tilted,
+    Open,
+}
+
+
+/// How comfortable the combination of temperature and humidity is, as judged by the sensor.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum HygrothermalComfort {
+    Good,
+    Medium,
+    Bad,
+}
+
+/// The assessment of the indoor air by a multi-function sensor.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum IndoorAirAnalysis {
+    Optimal,
+    Dry,
+    HighHumidity,
+    HighTemperatureAndHumidity,
+
+    /// Temperature or humidity are outside the range of the sensor.
+    Anomaly,
+}
+
+
+/// The fan speed selected on a room operating panel.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum FanSpeed {
+    Auto,
+    Stage0,
+    Stage1,
+    Stage2,
+    Stage3,
+}
+
+/// The additional input of a room operating panel, transmitted in the lowest bit.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+enum PanelInput {
+    None,
+    OccupancyButton,
+    SlideSwitch,
+    Contact,
+}
+
+
+/// The position of the sun in the sky.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct SunPosition {
+    /// Elevation above the horizon, -90° to +90°.
+    pub elevation_degrees: i16,
+
+    /// Azimuth, clockwise from north, 0° to 359°.
+    pub azimuth_degrees: u16,
+}
+
+/// A position on Earth.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct GeographicPosition {
+    /// Latitude in units of 0.01°, positive towards north.
+    pub latitude_hundredth_degrees: i32,
+
+    /// Longitude in units of 0.01°, positive towards east.
+    pub longitude_hundredth_degrees: i32,
+}
+
+
+/// A button on a rocker switch.
+///
+/// The EEP specification calls the buttons AI, A0, BI and B0.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum RockerButton {
+    AOn,
+    AOff,
+    BOn,
+    BOff,
+}
+impl RockerButton {
+    const fn from_bits(bits: u8) -> Self {
+        match bits & 0b11 {
+            0b00 => Self::AOn,
+            0b01 => Self::AOff,
+            0b10 => Self::BOn,
+            _ => Self::BOff,
+        }
+    }
+}
+
+/// What happened to the buttons on a rocker switch.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum RockerAction {
+    /// A single button has been pressed.
+    Pressed(RockerButton),
+
+    /// Two buttons have been pressed simultaneously.
+    PressedTwo(RockerButton, RockerButton),
+
+    /// Three or four buttons have been pressed simultaneously.
+    PressedMany,
+
+    /// All buttons have been released.
+    Released,
+}
+impl RockerAction {
+    /// Returns whether exactly the buttons `first` and `second` have been pressed together, in
+    /// either order.
+    pub fn is_both(&self, first: RockerButton, second: RockerButton) -> bool {
+        match *self {
+            Self::PressedTwo(a, b) => (a == first && b == second) || (a == second && b == first),
+            _ => false,
+        }
+    }
+}
+
+
+/// The values that have been decoded from a single telegram.
+///
+/// Values not transmitted by the given profile (or in the given telegram) are `None`.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Measurements {
+    pub temperature_tenth_celsius: Option<i32>,
+    pub relative_humidity_half_percent: Option<u8>,
+    pub illumination_lux: Option<u32>,
+    pub co2_ppm: Option<u16>,
+    pub acceleration_status: Option<AccelerationStatus>,
+
+    /// Acceleration along the X, Y and Z axes, in units of 0.001 g.
+    pub acceleration_milli_g: Option<[i16; 3]>,
+
+    pub contact: Option<ContactState>,
+    pub window_handle: Option<WindowState>,
+
+    /// Wind speed in units of 0.1 m/s.
+    pub wind_speed_tenth_mps: Option<u16>,
+
+    pub raining: Option<bool>,
+    pub night: Option<bool>,
+
+    /// Sun intensity from the west, south and east, in lux.
+    pub sun_intensity_lux: Option<[u32; 3]>,
+
+    pub sun_position: Option<SunPosition>,
+    pub geographic_position: Option<GeographicPosition>,
+    pub date: Option<Date>,
+    pub time: Option<TimeOfDay>,
+
+    /// How far a valve is open, 0% to 100%.
+    pub valve_position_percent: Option<u8>,
+
+    pub battery_low: Option<bool>,
+
+    /// The position of the setpoint dial of a room operating panel, from 0 (minimum) to 1000
+    /// (maximum).
+    pub setpoint_permille: Option<u16>,
+
+    pub fan_speed: Option<FanSpeed>,
+    pub occupancy_button_pressed: Option<bool>,
+
+    /// Whether the slide switch is in the day (on) position.
+    pub slide_switch_day: Option<bool>,
+
+    pub motion_detected: Option<bool>,
+
+    pub smoke_detected: Option<bool>,
+
+    /// Whether the sensor reports a fault of its own.
+    pub sensor_fault: Option<bool>,
+
+    pub maintenance_due: Option<bool>,
+    pub hygrothermal_comfort: Option<HygrothermalComfort>,
+    pub indoor_air: Option<IndoorAirAnalysis>,
+}
+impl Measurements {
+    /// Overwrites the values in `self` with those values that are known in `newer`.
+    pub fn update_from(&mut self, newer: &Measurements) {
+        macro_rules! update_fields {
+            ($($field:ident),* $(,)?) => {
+                $(
+                    if newer.$field.is_some() {
+                        self.$field = newer.$field;
+                    }
+                )*
+            };
+        }
+        update_fields!(
+            temperature_tenth_celsius,
+            relative_humidity_half_percent,
+            illumination_lux,
+            co2_ppm,
+            acceleration_status,
+            acceleration_milli_g,
+            contact,
+            window_handle,
+            wind_speed_tenth_mps,
+            raining,
+            night,
+            sun_intensity_lux,
+            sun_position,
+            geographic_position,
+            date,
+            time,
+            valve_position_percent,
+            battery_low,
+            setpoint_permille,
+            fan_speed,
+            occupancy_button_pressed,
+            slide_switch_day,
+            motion_detected,
+            smoke_detected,
+            sensor_fault,
+            maintenance_due,
+            hygrothermal_comfort,
+            indoor_air,
+        );
+    }
+}
+impl fmt::Display for Measurements {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(t) = self.temperature_tenth_celsius {
+            let sign = if t < 0 { "-" } else { "" };
+            write!(f, " temperature={}{}.{}C", sign, t.abs() / 10, t.abs() % 10)?;
+        }
+        if let Some(h) = self.relative_humidity_half_percent {
+            write!(f, " humidity={}.{}%", h / 2, if h % 2 == 0 { 0 } else { 5 })?;
+        }
+        if let Some(i) = self.illumination_lux {
+            write!(f, " illumination={}lx", i)?;
+        }
+        if let Some(c) = self.co2_ppm {
+            write!(f, " co2={}ppm", c)?;
+        }
+        if let Some(a) = self.acceleration_status {
+            write!(f, " acceleration_status={:?}", a)?;
+        }
+        if let Some([x, y, z]) = self.acceleration_milli_g {
+            write!(f, " acceleration={},{},{}mg", x, y, z)?;
+        }
+        if let Some(c) = self.contact {
+            write!(f, " contact={:?}", c)?;
+        }
+        if let Some(w) = self.window_handle {
+            write!(f, " window_handle={:?}", w)?;
+        }
+        if let Some(w) = self.wind_speed_tenth_mps {
+            write!(f, " wind={}.{}m/s", w / 10, w % 10)?;
+        }
+        if let Some(r) = self.raining {
+            write!(f, " raining={}", r)?;
+        }
+        if let Some(n) = self.night {
+            write!(f, " night={}", n)?;
+        }
+        if let Some([west, south, east]) = self.sun_intensity_lux {
+            write!(f, " sun={},{},{}lx", west, south, east)?;
+        }
+        if let Some(p) = self.sun_position {
+            write!(f, " sun_elevation={} sun_azimuth={}", p.elevation_degrees, p.azimuth_degrees)?;
+        }
+        if let Some(p) = self.geographic_position {
+            let (lat, lon) = (p.latitude_hundredth_degrees, p.longitude_hundredth_degrees);
+            write!(
+                f, " position={}{}.{:02},{}{}.{:02}",
+                if lat < 0 { "-" } else { "" }, lat.abs() / 100, lat.abs() % 100,
+                if lon < 0 { "-" } else { "" }, lon.abs() / 100, lon.abs() % 100,
+            )?;
+        }
+        if let Some(d) = self.date {
+            write!(f, " date={}", d)?;
+        }
+        if let Some(t) = self.time {
+            write!(f, " time={}", t)?;
+        }
+        if let Some(v) = self.valve_position_percent {
+            write!(f, " valve={}%", v)?;
+        }
+        if let Some(b) = self.battery_low {
+            write!(f, " battery_low={}", b)?;
+        }
+        if let Some(s) = self.setpoint_permille {
+            write!(f, " setpoint={}.{}%", s / 10, s % 10)?;
+        }
+        if let Some(s) = self.fan_speed {
+            write!(f, " fan_speed={:?}", s)?;
+        }
+        if let Some(o) = self.occupancy_button_pressed {
+            write!(f, " occupancy_button={}", o)?;
+        }
+        if let Some(s) = self.slide_switch_day {
+            write!(f, " slide_switch_day={}", s)?;
+        }
+        if let Some(m) = self.motion_detected {
+            write!(f, " motion={}", m)?;
+        }
+        if let Some(s) = self.smoke_detected {
+            write!(f, " smoke={}", s)?;
+        }
+        if let Some(s) = self.sensor_fault {
+            write!(f, " sensor_fault={}", s)?;
+        }
+        if let Some(m) = self.maintenance_due {
+            write!(f, " maintenance_due={}", m)?;
+        }
+        if let Some(c) = self.hygrothermal_comfort {
+            write!(f, " comfort={:?}", c)?;
+        }
+        if let Some(a) = self.indoor_air {
+            write!(f, " indoor_air={:?}", a)?;
+        }
+        Ok(())
+    }
+}
+
+
+/// Returns the RORG (telegram type) byte of the given profile.
+pub const fn rorg(profile: u32) -> u8 {
+    ((profile >> 16) & 0xFF) as u8
+}
+
+
+/// Extracts a bit field from telegram data.
+///
+/// As in the EEP specification, bit offset 0 is the most significant bit of the first byte.
+pub fn bits(data: &[u8], bit_offset: usize, bit_size: usize) -> u32 {
+    assert!(bit_size <= 32);
+    assert!(bit_offset + bit_size <= data.len() * 8);
+
+    let mut value = 0;
+    for bit_index in bit_offset..bit_offset+bit_size {
+        let byte = data[bit_index / 8];
+        let bit = (byte >> (7 - (bit_index % 8))) & 0b1;
+        value = (value << 1) | u32::from(bit);
+    }
+    value
+}
+
+
+/// Decodes the data and status bytes of a rocker switch telegram (F6-02-01 or F6-02-02).
+///
+/// Returns `None` if the telegram does not originate from a rocker switch or its contents are
+/// not meaningful.
+pub fn decode_rocker(data: u8, status: u8) -> Option<RockerAction> {
+    // data: RRRE_rrrS (first action, energy bow, second action, second action valid)
+    // status: 00TN_xxxx (T21, NU, repeater count)
+    let t21 = status & 0b0010_0000 != 0;
+    let nu = status & 0b0001_0000 != 0;
+    if !t21 {
+        // not a PTM 2xx module
+        return None;
+    }
+
+    let energy_bow_pressed = data & 0b0001_0000 != 0;
+    if nu {
+        // N-message: the buttons are identified
+        if !energy_bow_pressed {
+            // EEP says to ignore the buttons when the energy bow is released
+            return Some(RockerAction::Released);
+        }
+
+        let first_button = RockerButton::from_bits(data >> 5);
+        if data & 0b0000_0001 != 0 {
+            let second_button = RockerButton::from_bits(data >> 1);
+            Some(RockerAction::PressedTwo(first_button, second_button))
+        } else {
+            Some(RockerAction::Pressed(first_button))
+        }
+    } else {
+        // U-message: only the number of buttons is known
+        if !energy_bow_pressed {
+            return Some(RockerAction::Released);
+        }
+        match data >> 5 {
+            0b011 => Some(RockerAction::PressedMany),
+            _ => None, // no or an unknown number of buttons
+        }
+    }
+}
+
+
+/// Returns the response to a 4BS (A5) teach-in query, or `None` if the data is not a teach-in
+/// query that includes the profile.
+///
+/// The response confirms that the profile is supported and the sender has been stored.
+pub fn teach_in_response(query: &[u8]) -> Option<[u8; 4]> {
+    // FFFF_FFTT TTTT_TMMM MMMM_MMMM ERSN_L000
+    // (FUNC, TYPE, manufacturer, with EEP, EEP result, LRN result, status: query/response, LRN)
+    let query: [u8; 4] = query.try_into().ok()?;
+    if query[3] & 0b0000_1000 != 0 {
+        // not a teach-in telegram
+        return None;
+    }
+    if query[3] & 0b1000_0000 == 0 || query[3] & 0b0001_0000 != 0 {
+        // no profile included or not a query
+        return None;
+    }
+    Some([query[0], query[1], query[2], 0b1111_0000])
+}
+
+
+/// Returns the data of the telegram with which a valve actuator (A5-20-01) is answered.
+///
+/// The valve regulates towards the setpoint, assuming the given room temperature. Both are in
+/// units of 0.1 °C and clamped to the range of 0 °C to +40 °C.
+pub fn valve_reply(setpoint_tenth_celsius: i32, room_tenth_celsius: i32) -> [u8; 4] {
+    // SSSS_SSSS RRRR_RRRR 0000_0P00 0000_1000
+    // (setpoint, room temperature, setpoint is a temperature, data telegram)
+
+    // 8 bits of setpoint mapping [0; 255] to [0 °C; +40 °C]
+    let setpoint_bits = (setpoint_tenth_celsius.clamp(0, 400) * 255 + 200) / 400;
+
+    // 8 bits of room temperature mapping [0; 255] to [+40 °C; 0 °C]
+    let room_bits = 255 - (room_tenth_celsius.clamp(0, 400) * 255 + 200) / 400;
+
+    [setpoint_bits as u8, room_bits as u8, 0b0000_0100, 0b0000_1000]
+}
+
+
+/// A reason why a telegram could not be decoded.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum DecodeError {
+    /// Telegrams of the profile cannot be decoded.
+    UnknownProfile,
+
+    /// The telegram has the wrong length, contains invalid values or is a teach-in telegram.
+    InvalidTelegram,
+}
+
+
+/// Decodes the data of a telegram in the given profile.
+///
+/// Fails with [`DecodeError::UnknownProfile`] if telegrams of the profile cannot be decoded at all,
+/// regardless of their data.
+pub fn decode(profile: u32, data_slice: &[u8]) -> Result<Measurements, DecodeError> {
+    let mut measurements = Measurements::default();
+
+    if profile == 0xA5_09_04 {
+        // HHHH_HHHH CCCC_CCCC TTTT_TTTT 0000_LHT0
+        let data = match data_slice.try_into() {
+            Ok(ds) => u32::from_be_bytes(ds),
+            Err(_) => {
+                // wrong format
+                return Err(DecodeError::InvalidTelegram);
+            },
+        };
+
+        if data & 0b1000 == 0 {
+            // this is a teach-in packet, ignore it
+            return Err(DecodeError::InvalidTelegram);
+        }
+
+        // 8 bits of relative humidity in units of 0.5%, if the sensor has a humidity sensor
+        if data & 0b0100 != 0 {
+            let humidity_bits = ((data >> 24) & 0xFF) as u8;
+            if humidity_bits <= 200 {
+                measurements.relative_humidity_half_percent = Some(humidity_bits);
+            }
+        }
+
+        // 8 bits of CO2 concentration in units of 10 ppm
+        let co2_bits = ((data >> 16) & 0xFF) as u16;
+        measurements.co2_ppm = Some(co2_bits * 10);
+
+        // 8 bits of temperature in units of 0.2 °C, if the sensor has a temperature sensor
+        if data & 0b0010 != 0 {
+            let temperature_bits = ((data >> 8) & 0xFF) as i32;
+            measurements.temperature_tenth_celsius = Some(temperature_bits * 2);
+        }
+    } else if profile == 0xA5_09_08 {
+        // 0000_0000 0000_0000 CCCC_CCCC 0000_L000
+        let data = match data_slice.try_into() {
+            Ok(ds) => u32::from_be_bytes(ds),
+            Err(_) => {
+                // wrong format
+                return Err(DecodeError::InvalidTelegram);
+            },
+        };
+
+        if data & 0b1000 == 0 {
+            // this is a teach-in packet, ignore it
+            return Err(DecodeError::InvalidTelegram);
+        }
+
+        // 8 bits of CO2 concentration mapping [0; 255] to [0 ppm; 2000 ppm]
+        let co2_bits = (data >> 8) & 0xFF;
+        measurements.co2_ppm = Some(((co2_bits * 2000 + 127) / 255) as u16);
+    } else if profile == 0xD2_14_40 || profile == 0xD2_14_41 {
+        // TTTT_TTTT TTHH_HHHH HHII_IIII IIII_IIII IIIA_AXXX XXXX_XXXY YYYY_YYYY YZZZ_ZZZZ ZZZC_0000
+        // (D2-14-40 does not transmit the contact bit)
+        if data_slice.len() != 9 {
+            // wrong format
+            return Err(DecodeError::InvalidTelegram);
+        }
+
+        // 10 bits of temperature mapping [0; 1000] to [-40 °C; +60 °C]
+        // pretty easy to derive tenths of degrees
+        let temperature_bits = bits(data_slice, 0, 10);
+        if temperature_bits <= 1000 {
+            measurements.temperature_tenth_celsius = Some(temperature_bits as i32 - 400);
+        }
+
+        // 8 bits of relative humidity mapping [0; 200] to [0%; 100%]
+        let humidity_bits = bits(data_slice, 10, 8);
+        if humidity_bits <= 200 {
+            measurements.relative_humidity_half_percent = Some(humidity_bits as u8);
+        }
+
+        // 17 bits of illumination mapping [0; 100_000] to [0 lx; 100_000 lx]
+        let illumination_bits = bits(data_slice, 18, 17);
+        if illumination_bits <= 100_000 {
+            measurements.illumination_lux = Some(illumination_bits);
+        }
+
+        measurements.acceleration_status = match bits(data_slice, 35, 2) {
+            0b00 => Some(AccelerationStatus::PeriodicUpdate),
+            0b01 => Some(AccelerationStatus::Threshold1Exceeded),
+            0b10 => Some(AccelerationStatus::Threshold2Exceeded),
+            _ => None, // reserved
+        };
+
+        // 10 bits per axis mapping [0; 1000] to [-2.5 g; +2.5 g]
+        let x_bits = bits(data_slice, 37, 10);
+        let y_bits = bits(data_slice, 47, 10);
+        let z_bits = bits(data_slice, 57, 10);
+        if x_bits <= 1000 && y_bits <= 1000 && z_bits <= 1000 {
+            measurements.acceleration_milli_g = Some([
+                (x_bits as i16) * 5 - 2500,
+                (y_bits as i16) * 5 - 2500,
+                (z_bits as i16) * 5 - 2500,
+            ]);
+        }
+
+        if profile == 0xD2_14_41 {
+            measurements.contact = if bits(data_slice, 67, 1) == 0 {
+                Some(ContactState::Open)
+            } else {
+                Some(ContactState::Closed)
+            };
+        }
+    } else if profile == 0xD2_14_30 {
+        // smoke, air quality, hygrothermal comfort, temperature and humidity sensor
+        // SFMH_Rwww wwww_wEEm mmmm_mmmT TTTT_TTTH HHHH_HHHC CAAA_0000
+        // (smoke alarm, sensor fault, maintenance due, humidity out of range, temperature out of
+        // range, weeks since last maintenance, energy storage, months of remaining product life,
+        // temperature, humidity, hygrothermal comfort index, indoor air analysis)
+        if data_slice.len() != 6 {
+            // wrong format
+            return Err(DecodeError::InvalidTelegram);
+        }
+
+        measurements.smoke_detected = Some(bits(data_slice, 0, 1) != 0);
+        measurements.sensor_fault = Some(bits(data_slice, 1, 1) != 0);
+        measurements.maintenance_due = Some(bits(data_slice, 2, 1) != 0);
+
+        // energy storage: high, medium, low, critical
+        measurements.battery_low = Some(bits(data_slice, 13, 2) >= 0b10);
+
+        // 8 bits of temperature mapping [0; 250] to [0 °C; +50 °C]
+        let temperature_bits = bits(data_slice, 23, 8);
+        if temperature_bits <= 250 {
+            measurements.temperature_tenth_celsius = Some((temperature_bits * 2) as i32);
+        }
+
+        // 8 bits of relative humidity mapping [0; 200] to [0%; 100%]
+        let humidity_bits = bits(data_slice, 31, 8);
+        if humidity_bits <= 200 {
+            measurements.relative_humidity_half_percent = Some(humidity_bits as u8);
+        }
+
+        measurements.hygrothermal_comfort = match bits(data_slice, 39, 2) {
+            0b00 => Some(HygrothermalComfort::Good),
+            0b01 => Some(HygrothermalComfort::Medium),
+            0b10 => Some(HygrothermalComfort::Bad),
+            _ => None, // error
+        };
+
+        measurements.indoor_air = match bits(data_slice, 41, 3) {
+            0b000 => Some(IndoorAirAnalysis::Optimal),
+            0b001 => Some(IndoorAirAnalysis::Dry),
+            0b010 => Some(IndoorAirAnalysis::HighHumidity),
+            0b011 => Some(IndoorAirAnalysis::HighTemperatureAndHumidity),
+            0b100 => Some(IndoorAirAnalysis::Anomaly),
+            _ => None, // reserved or error
+        };
+    } else if profile & 0xFF_FF_00 == 0xA5_13_00 {
+        // weather stations send a sequence of telegrams of different types;
+        // the type is identified in the telegram itself
+        if data_slice.len() != 4 {
+            // wrong format
+            return Err(DecodeError::InvalidTelegram);
+        }
+
+        if bits(data_slice, 28, 1) == 0 {
+            // this is a teach-in packet, ignore it
+            return Err(DecodeError::InvalidTelegram);
+        }
+
+        match bits(data_slice, 24, 4) {
+            0x1 => {
+                // A5-13-01 weather station
+                // DDDD_DDDD TTTT_TTTT WWWW_WWWW 0001_LNR0
+
+                // 8 bits of dawn sensor mapping [0; 255] to [0 lx; 999 lx]
+                let dawn_bits = bits(data_slice, 0, 8);
+                measurements.illumination_lux = Some((dawn_bits * 999 + 127) / 255);
+
+                // 8 bits of temperature mapping [0; 255] to [-40 °C; +80 °C]
+                let temperature_bits = bits(data_slice, 8, 8);
+                measurements.temperature_tenth_celsius = Some(((temperature_bits * 1200 + 127) / 255) as i32 - 400);
+
+                // 8 bits of wind speed mapping [0; 255] to [0 m/s; 70 m/s]
+                let wind_bits = bits(data_slice, 16, 8);
+                measurements.wind_speed_tenth_mps = Some(((wind_bits * 700 + 127) / 255) as u16);
+
+                measurements.night = Some(bits(data_slice, 29, 1) != 0);
+                measurements.raining = Some(bits(data_slice, 30, 1) != 0);
+            },
+            0x2 => {
+                // A5-13-02 sun intensity
+                // WWWW_WWWW SSSS_SSSS EEEE_EEEE 0010_LH00
+
+                // 8 bits each mapping [0; 255] to [0 lx; 150 klx]
+                let west_bits = bits(data_slice, 0, 8);
+                let south_bits = bits(data_slice, 8, 8);
+                let east_bits = bits(data_slice, 16, 8);
+                measurements.sun_intensity_lux = Some([
+                    (west_bits * 150_000 + 127) / 255,
+                    (south_bits * 150_000 + 127) / 255,
+                    (east_bits * 150_000 + 127) / 255,
+                ]);
+            },
+            0x3 => {
+                // A5-13-03 date exchange
+                // 000D_DDDD 0000_MMMM 0YYY_YYYY 0011_L00S
+                let day = bits(data_slice, 3, 5) as u8;
+                let month = bits(data_slice, 12, 4) as u8;
+                let year = 2000 + bits(data_slice, 17, 7) as u16;
+                measurements.date = Some(Date::new(year, month, day).ok_or(DecodeError::InvalidTelegram)?);
+            },
+            0x4 => {
+                // A5-13-04 time and day exchange
+                // WWWH_HHHH 00MM_MMMM 00SS_SSSS 0100_LFPS
+                let weekday = bits(data_slice, 0, 3) as u8;
+                let mut hour = bits(data_slice, 3, 5) as u8;
+                let minute = bits(data_slice, 10, 6) as u8;
+                let second = bits(data_slice, 18, 6) as u8;
+                if bits(data_slice, 29, 1) != 0 {
+                    // 12-hour format; convert to 24-hour format
+                    if !(1..=12).contains(&hour) {
+                        return Err(DecodeError::InvalidTelegram);
+                    }
+                    let pm = bits(data_slice, 30, 1) != 0;
+                    hour %= 12;
+                    if pm {
+                        hour += 12;
+                    }
+                }
+                measurements.time = Some(TimeOfDay::new(weekday, hour, minute, second).ok_or(DecodeError::InvalidTelegram)?);
+            },
+            0x5 => {
+                // A5-13-05 direction exchange
+                // EEEE_EEEE 0000_000A AAAA_AAAA 0101_L000
+
+                // 8 bits of elevation mapping [0; 180] to [-90°; +90°]
+                // 9 bits of azimuth [0; 359]
+                let elevation_bits = bits(data_slice, 0, 8);
+                let azimuth_bits = bits(data_slice, 15, 9);
+                if elevation_bits > 180 || azimuth_bits > 359 {
+                    return Err(DecodeError::InvalidTelegram);
+                }
+                measurements.sun_position = Some(SunPosition {
+                    elevation_degrees: elevation_bits as i16 - 90,
+                    azimuth_degrees: azimuth_bits as u16,
+                });
+            },
+            0x6 => {
+                // A5-13-06 geographic position exchange
+                // aaaa_oooo AAAA_AAAA OOOO_OOOO 0110_L000
+                // (latitude and longitude each split into 4 most and 8 least significant bits)
+
+                // 12 bits of latitude mapping [0; 4095] to [-90°; +90°]
+                // 12 bits of longitude mapping [0; 4095] to [-180°; +180°]
+                let latitude_bits = (bits(data_slice, 0, 4) << 8) | bits(data_slice, 8, 8);
+                let longitude_bits = (bits(data_slice, 4, 4) << 8) | bits(data_slice, 16, 8);
+                measurements.geographic_position = Some(GeographicPosition {
+                    latitude_hundredth_degrees: ((latitude_bits * 18_000 + 2047) / 4095) as i32 - 9_000,
+                    longitude_hundredth_degrees: ((longitude_bits * 36_000 + 2047) / 4095) as i32 - 18_000,
+                });
+            },
+            _ => {
+                // unknown telegram type
+                return Err(DecodeError::InvalidTelegram);
+            },
+        }
+    } else if profile == 0xA5_07_01 || profile == 0xA5_07_02 || profile == 0xA5_07_03 {
+        // A5-07-01: VVVV_VVVV 0000_0000 PPPP_PPPP 0000_L00v
+        // A5-07-02: VVVV_VVVV 0000_0000 0000_0000 P000_L000
+        // A5-07-03: VVVV_VVVV IIII_IIII II00_0000 P000_L000
+        if data_slice.len() != 4 {
+            // wrong format
+            return Err(DecodeError::InvalidTelegram);
+        }
+
+        if bits(data_slice, 28, 1) == 0 {
+            // this is a teach-in packet, ignore it
+            return Err(DecodeError::InvalidTelegram);
+        }
+
+        if profile == 0xA5_07_01 {
+            // 8 bits of PIR status: [0; 127] uncertain, [128; 255] motion detected
+            measurements.motion_detected = Some(bits(data_slice, 16, 8) >= 128);
+        } else {
+            measurements.motion_detected = Some(bits(data_slice, 24, 1) == 1);
+        }
+
+        if profile == 0xA5_07_03 {
+            // 10 bits of illumination [0; 1000] in lux
+            let illumination_bits = bits(data_slice, 8, 10);
+            if illumination_bits <= 1000 {
+                measurements.illumination_lux = Some(illumination_bits);
+            }
+        }
+    } else if profile == 0xA5_08_01 || profile == 0xA5_08_02 || profile == 0xA5_08_03 {
+        // VVVV_VVVV IIII_IIII TTTT_TTTT 0000_LPO0
+        // (supply voltage, illumination, temperature, PIR, occupancy button)
+        if data_slice.len() != 4 {
+            // wrong format
+            return Err(DecodeError::InvalidTelegram);
+        }
+
+        if bits(data_slice, 28, 1) == 0 {
+            // this is a teach-in packet, ignore it
+            return Err(DecodeError::InvalidTelegram);
+        }
+
+        // 8 bits of illumination mapping [0; 255] to [0 lx; 510/1020/1530 lx]
+        // 8 bits of temperature mapping [0; 255] to [0 °C; +51 °C] or [-30 °C; +50 °C]
+        let illumination_bits = bits(data_slice, 8, 8);
+        let temperature_bits = bits(data_slice, 16, 8);
+        let (max_lux, min_tenth_celsius, max_tenth_celsius) = match profile {
+            0xA5_08_01 => (510, 0, 510),
+            0xA5_08_02 => (1020, 0, 510),
+            _ => (1530, -300, 500),
+        };
+        measurements.illumination_lux = Some((illumination_bits * max_lux + 127) / 255);
+        let temperature_span = (max_tenth_celsius - min_tenth_celsius) as u32;
+        measurements.temperature_tenth_celsius = Some(((temperature_bits * temperature_span + 127) / 255) as i32 + min_tenth_celsius);
+
+        // PIR: 0 = on (motion detected), 1 = off
+        measurements.motion_detected = Some(bits(data_slice, 30, 1) == 0);
+
+        // occupancy button: 0 = pressed, 1 = released
+        measurements.occupancy_button_pressed = Some(bits(data_slice, 31, 1) == 0);
+    } else if matches!(profile, 0xA5_10_01..=0xA5_10_0D | 0xA5_10_10..=0xA5_10_17) {
+        // room operating panels
+        // (A5-10-18 to A5-10-23 lay out illumination, supply voltage, occupancy enable and special
+        // heating states differently and are not decoded yet; they end up as unknown profiles)
+        if data_slice.len() != 4 {
+            // wrong format
+            return Err(DecodeError::InvalidTelegram);
+        }
+
+        if bits(data_slice, 28, 1) == 0 {
+            // this is a teach-in packet, ignore it
+            return Err(DecodeError::InvalidTelegram);
+        }
+
+        let panel_input = match profile & 0xFF {
+            0x01..=0x0D => {
+                // FFFF_FFFF SSSS_SSSS TTTT_TTTT 0000_L00X
+                let (has_fan, has_setpoint, panel_input) = match profile & 0xFF {
+                    0x01 => (true, true, PanelInput::OccupancyButton),
+                    0x02 => (true, true, PanelInput::SlideSwitch),
+                    0x03 => (false, true, PanelInput::None),
+                    0x04 => (true, true, PanelInput::None),
+                    0x05 => (false, true, PanelInput::OccupancyButton),
+                    0x06 => (false, true, PanelInput::SlideSwitch),
+                    0x07 => (true, false, PanelInput::None),
+                    0x08 => (true, false, PanelInput::OccupancyButton),
+                    0x09 => (true, false, PanelInput::SlideSwitch),
+                    0x0A => (false, true, PanelInput::Contact),
+                    0x0B => (false, false, PanelInput::Contact),
+                    0x0C => (false, false, PanelInput::OccupancyButton),
+                    _ => (false, false, PanelInput::SlideSwitch),
+                };
+
+                if has_fan {
+                    measurements.fan_speed = Some(match bits(data_slice, 0, 8) {
+                        210..=255 => FanSpeed::Auto,
+                        190..=209 => FanSpeed::Stage0,
+                        165..=189 => FanSpeed::Stage1,
+                        145..=164 => FanSpeed::Stage2,
+                        _ => FanSpeed::Stage3,
+                    });
+                }
+
+                // 8 bits of setpoint [0; 255]
+                if has_setpoint {
+                    let setpoint_bits = bits(data_slice, 8, 8);
+                    measurements.setpoint_permille = Some(((setpoint_bits * 1000 + 127) / 255) as u16);
+                }
+
+                // 8 bits of temperature mapping [255; 0] to [0 °C; +40 °C] (sic!)
+                let temperature_bits = bits(data_slice, 16, 8);
+                measurements.temperature_tenth_celsius = Some((((255 - temperature_bits) * 400 + 127) / 255) as i32);
+
+                panel_input
+            },
+            0x10..=0x14 => {
+                // SSSS_SSSS HHHH_HHHH TTTT_TTTT 0000_L00X
+                let (has_setpoint, panel_input) = match profile & 0xFF {
+                    0x10 => (true, PanelInput::OccupancyButton),
+                    0x11 => (true, PanelInput::SlideSwitch),
+                    0x12 => (true, PanelInput::None),
+                    0x13 => (false, PanelInput::OccupancyButton),
+                    _ => (false, PanelInput::SlideSwitch),
+                };
+
+                // 8 bits of setpoint [0; 255]
+                if has_setpoint {
+                    let setpoint_bits = bits(data_slice, 0, 8);
+                    measurements.setpoint_permille = Some(((setpoint_bits * 1000 + 127) / 255) as u16);
+                }
+
+                // 8 bits of relative humidity mapping [0; 250] to [0%; 100%]
+                let humidity_bits = bits(data_slice, 8, 8);
+                if humidity_bits <= 250 {
+                    measurements.relative_humidity_half_percent = Some(((humidity_bits * 4 + 2) / 5) as u8);
+                }
+
+                // 8 bits of temperature mapping [0; 250] to [0 °C; +40 °C]
+                let temperature_bits = bits(data_slice, 16, 8);
+                if temperature_bits <= 250 {
+                    measurements.temperature_tenth_celsius = Some((temperature_bits * 8 / 5) as i32);
+                }
+
+                panel_input
+            },
+            0x15..=0x17 => {
+                // 0000_0000 SSSS_SSTT TTTT_TTTT 0000_L00X
+                let (has_setpoint, panel_input) = match profile & 0xFF {
+                    0x15 => (true, PanelInput::None),
+                    0x16 => (true, PanelInput::OccupancyButton),
+                    _ => (false, PanelInput::OccupancyButton),
+                };
+
+                // 6 bits of setpoint [0; 63]
+                if has_setpoint {
+                    let setpoint_bits = bits(data_slice, 8, 6);
+                    measurements.setpoint_permille = Some(((setpoint_bits * 1000 + 31) / 63) as u16);
+                }
+
+                // 10 bits of temperature mapping [1023; 0] to [-10 °C; +41.2 °C] (sic!)
+                let temperature_bits = bits(data_slice, 14, 10);
+                measurements.temperature_tenth_celsius = Some((((1023 - temperature_bits) * 512 + 511) / 1023) as i32 - 100);
+
+                panel_input
+            },
+            _ => {
+                // don't know this panel
+                return Err(DecodeError::UnknownProfile);
+            },
+        };
+
+        let input_bit = bits(data_slice, 31, 1);
+        match panel_input {
+            PanelInput::None => {},
+            PanelInput::OccupancyButton => {
+                measurements.occupancy_button_pressed = Some(input_bit == 0);
+            },
+            PanelInput::SlideSwitch => {
+                measurements.slide_switch_day = Some(input_bit == 1);
+            },
+            PanelInput::Contact => {
+                measurements.contact = if input_bit == 0 {
+                    Some(ContactState::Closed)
+                } else {
+                    Some(ContactState::Open)
+                };
+            },
+        }
+    } else if profile == 0xA5_20_01 {
+        // PPPP_PPPP xxxB_xxxx TTTT_TTTT 0000_L000
+        if data_slice.len() != 4 {
+            // wrong format
+            return Err(DecodeError::InvalidTelegram);
+        }
+
+        if bits(data_slice, 28, 1) == 0 {
+            // this is a teach-in packet, ignore it
+            return Err(DecodeError::InvalidTelegram);
+        }
+
+        // 8 bits of valve position [0; 100] in percent
+        let position_bits = bits(data_slice, 0, 8);
+        if position_bits <= 100 {
+            measurements.valve_position_percent = Some(position_bits as u8);
+        }
+
+        // battery capacity: 1 = ok, 0 = change battery in the next days
+        measurements.battery_low = Some(bits(data_slice, 11, 1) == 0);
+
+        // 8 bits of temperature mapping [0; 255] to [0 °C; +40 °C]
+        let temperature_bits = bits(data_slice, 16, 8);
+        measurements.temperature_tenth_celsius = Some(((temperature_bits * 400 + 127) / 255) as i32);
+    } else if profile == 0xF6_10_00 {
+        // HHHH_0000
+        if data_slice.len() != 1 {
+            // wrong format
+            return Err(DecodeError::InvalidTelegram);
+        }
+
+        measurements.window_handle = match data_slice[0] & 0b1111_0000 {
+            0b1100_0000|0b1110_0000 => Some(WindowState::Open), // handle horizontal
+            0b1111_0000 => Some(WindowState::Tilted), // handle up
+            0b1101_0000 => Some(WindowState::Closed), // handle down
+            _ => return Err(DecodeError::InvalidTelegram),
+        };
+    } else if profile == 0xD5_00_01 {
+        // 0000_L00C
+        if data_slice.len() != 1 {
+            // wrong format
+            return Err(DecodeError::InvalidTelegram);
+        }
+
+        if data_slice[0] & 0b1000 == 0 {
+            // this is a teach-in packet, ignore it
+            return Err(DecodeError::InvalidTelegram);
+        }
+
+        measurements.contact = if data_slice[0] & 0b0001 == 0 {
+            Some(ContactState::Open)
+        } else {
+            Some(ContactState::Closed)
+        };
+    } else if let Some(definition) = crate::generic_eep::find(profile) {
+        // the build script has generated a description of this format
+        return crate::generic_eep::decode(definition, data_slice)
+            .ok_or(DecodeError::InvalidTelegram);
+    } else {
+        // don't know how to decode this format
+        return Err(DecodeError::UnknownProfile);
+    }
+
+    Ok(measurements)
+}
@@ -1,10 +1,12 @@
-//! EnOcean Serial Protocol 3 packet decoding routines.
+//! EnOcean Serial Protocol 3 packet decoding and encoding routines.
 
 
+use critical_section::Mutex;
 use from_to_repr::from_to_other;
 use stm32f7::stm32f745::Peripherals;
+use vcell::VolatileCell;
 
-use crate::crc8::crc8;
+use crate::crc8::{crc8, crc8_continue};
 use crate::uart::{Uart, Usart2};
 
 
@@ -13,6 +15,21 @@ const SYNC_BYTE: u8 = 0x55;
 type EnoceanUart = Usart2;
 
 
+/// The base ID of the EnOcean module, which we use as the sender ID of outgoing telegrams.
+///
+/// Read from the module once it reports that it is ready.
+static BASE_ID: Mutex<VolatileCell<Option<u32>>> = Mutex::new(VolatileCell::new(None));
+
+/// The number of packets written to the EnOcean module whose responses are still outstanding.
+///
+/// The module answers every packet with exactly one response, in order.
+static OUTSTANDING_RESPONSES: Mutex<VolatileCell<u8>> = Mutex::new(VolatileCell::new(0));
+
+/// How many outstanding responses precede the response to the command reading the base ID, if
+/// that command is outstanding.
+static BASE_ID_RESPONSE_POSITION: Mutex<VolatileCell<Option<u8>>> = Mutex::new(VolatileCell::new(None));
+
+
 #[derive(Clone, Copy, Debug)]
 #[from_to_other(base_type = u8, derive_compare = "as_int")]
 pub enum PacketType {
@@ -247,28 +264,72 @@ pub(crate) fn process_one_packet(peripherals: &Peripherals) -> PacketResult {
                 // any interesting event?
                 match EventType::from_base_type(data_slice[0]) {
                     EventType::Ready => {
+                        // the module has (re)started and will not answer anything sent before
+                        critical_section::with(|cs| {
+                            OUTSTANDING_RESPONSES.borrow(cs).set(0);
+                            BASE_ID_RESPONSE_POSITION.borrow(cs).set(None);
+                        });
+
                         // good morning! switch to transparent mode
-                        let mut set_transparent_mode_packet = [
-                            0x55, // sync byte
-                            0x00, 0x02, // 2 bytes data length
-                            0x00, // 0 bytes optional length
-                            PacketType::CommonCommand.to_base_type(),
-                            0x00, // CRC8H placeholder
-                            CommonCommandType::WriteTransparentMode.to_base_type(),
-                            0x01, // enable transparent mode
-                            0x00, // CRC8D placeholder
-                        ];
-                        let crc8h = crc8(&set_transparent_mode_packet[1..5]);
-                        let crc8d = crc8(&set_transparent_mode_packet[6..8]);
-                        set_transparent_mode_packet[5] = crc8h;
-                        set_transparent_mode_packet[8] = crc8d;
-
-                        EnoceanUart::write(peripherals, &set_transparent_mode_packet);
+                        write_packet(
+                            peripherals,
+                            PacketType::CommonCommand,
+                            &[
+                                CommonCommandType::WriteTransparentMode.to_base_type(),
+                                0x01, // enable transparent mode
+                            ],
+                            &[],
+                        );
+
+                        // find out which sender IDs we may use
+                        let position = critical_section::with(|cs| {
+                            OUTSTANDING_RESPONSES.borrow(cs).get()
+                        });
+                        write_packet(
+                            peripherals,
+                            PacketType::CommonCommand,
+                            &[CommonCommandType::ReadIdBase.to_base_type()],
+                            &[],
+                        );
+                        critical_section::with(|cs| {
+                            BASE_ID_RESPONSE_POSITION.borrow(cs).set(Some(position));
+                        });
                     },
                     _ => {},
                 }
             }
         },
+        PacketType::Response => {
+            // responses arrive in the order of the packets they answer
+            let answers_base_id_read = critical_section::with(|cs| {
+                let outstanding = OUTSTANDING_RESPONSES.borrow(cs);
+                outstanding.set(outstanding.get().saturating_sub(1));
+
+                let position = BASE_ID_RESPONSE_POSITION.borrow(cs);
+                match position.get() {
+                    Some(0) => {
+                        position.set(None);
+                        true
+                    },
+                    Some(p) => {
+                        position.set(Some(p - 1));
+                        false
+                    },
+                    None => false,
+                }
+            });
+
+            // the base ID follows the return code (0x00 = OK);
+            // base IDs are always in the range FF800000 to FFFFFF80
+            if answers_base_id_read && data_slice.len() == 5 && data_slice[0] == 0x00 {
+                let base_id = u32::from_be_bytes(data_slice[1..5].try_into().unwrap());
+                if base_id >= 0xFF80_0000 && base_id & 0x7F == 0 {
+                    critical_section::with(|cs| {
+                        BASE_ID.borrow(cs).set(Some(base_id))
+                    });
+                }
+            }
+        },
         _ => {},
     }
 
@@ -284,3 +345,67 @@ pub(crate) fn process_one_packet(peripherals: &Peripherals) -> PacketResult {
         },
     }
 }
+
+
+/// Writes an ESP3 packet to the EnOcean module.
+fn write_packet(peripherals: &Peripherals, packet_type: PacketType, data: &[u8], optional_data: &[u8]) {
+    let data_length: u16 = data.len().try_into().unwrap();
+    let optional_length: u8 = optional_data.len().try_into().unwrap();
+
+    let mut header = [
+        SYNC_BYTE,
+        (data_length >> 8) as u8,
+        (data_length & 0xFF) as u8,
+        optional_length,
+        packet_type.to_base_type(),
+        0x00, // CRC8H placeholder
+    ];
+    header[5] = crc8(&header[1..5]);
+    let crc8d = crc8_continue(optional_data, crc8(data));
+
+    EnoceanUart::write(peripherals, &header);
+    EnoceanUart::write(peripherals, data);
+    EnoceanUart::write(peripherals, optional_data);
+    EnoceanUart::write(peripherals, &[crc8d]);
+
+    critical_section::with(|cs| {
+        let outstanding = OUTSTANDING_RESPONSES.borrow(cs);
+        outstanding.set(outstanding.get().saturating_add(1));
+    });
+}
+
+
+/// Sends a radio telegram with the given RORG and data to the device with the given ID.
+///
+/// The base ID of the EnOcean module is used as the sender ID. Returns `false` if the base ID is
+/// not known yet, in which case nothing is sent.
+pub(crate) fn send_radio_telegram(peripherals: &Peripherals, rorg: u8, data: &[u8], destination: u32) -> bool {
+    let base_id_opt = critical_section::with(|cs| {
+        BASE_ID.borrow(cs).get()
+    });
+    let Some(base_id) = base_id_opt else {
+        return false;
+    };
+
+    // RORG, data, sender, status
+    let mut erp1_buffer = [0u8; 1 + 14 + 4 + 1];
+    let erp1_length = 1 + data.len() + 4 + 1;
+    let erp1 = &mut erp1_buffer[..erp1_length];
+    erp1[0] = rorg;
+    erp1[1..1+data.len()].copy_from_slice(data);
+    erp1[1+data.len()..1+data.len()+4].copy_from_slice(&base_id.to_be_bytes());
+    erp1[erp1_length-1] = 0x00;
+
+    // number of subtelegrams (3 when sending), destination, signal strength (0xFF when sending),
+    // security level (0 = unencrypted)
+    let destination_bytes = destination.to_be_bytes();
+    let optional_data = [
+        0x03,
+        destination_bytes[0], destination_bytes[1], destination_bytes[2], destination_bytes[3],
+        0xFF,
+        0x00,
+    ];
+
+    write_packet(peripherals, PacketType::RadioErp1, erp1, &optional_data);
+    true
+}
@@ -0,0 +1,133 @@
+//! Table-driven decoding of EnOcean Equipment Profiles.
+//!
+//! The tables are generated by the build script from the EEP definitions in `eep/eep.xml`; every
+//! profile defined there whose measurements can be interpreted is included and can be selected for
+//! a sensor slot at runtime. The build script only accepts fields in the units that `decode`
+//! assumes: °C, %, lx and ppm.
+
+
+use crate::eep::{bits, Measurements};
+
+
+/// The physical quantity transmitted in a data field.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Quantity {
+    Temperature,
+    RelativeHumidity,
+    Illumination,
+    Co2,
+
+    /// 0 for teach-in telegrams, 1 for data telegrams.
+    LearnBit,
+}
+
+
+/// A data field within a telegram.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct FieldDefinition {
+    pub quantity: Quantity,
+
+    /// The offset of the field, counted from the most significant bit of the first byte.
+    pub bit_offset: u16,
+
+    pub bit_size: u8,
+
+    /// The raw values corresponding to the minimum and the maximum of the scale.
+    ///
+    /// The first value may be larger than the second one if the scale is inverted.
+    pub range: (i32, i32),
+
+    /// The minimum and maximum of the scale, in thousandths of the unit.
+    pub scale_milli: (i32, i32),
+}
+impl FieldDefinition {
+    /// Extracts the value of this field from the data and scales it, returning thousandths of the
+    /// unit. Returns `None` if the raw value is outside the range.
+    pub fn decode_milli(&self, data: &[u8]) -> Option<i64> {
+        let raw = i64::from(bits(data, self.bit_offset.into(), self.bit_size.into()));
+
+        let (range_from, range_to) = (i64::from(self.range.0), i64::from(self.range.1));
+        let (scale_from, scale_to) = (i64::from(self.scale_milli.0), i64::from(self.scale_milli.1));
+        if raw < range_from.min(range_to) || raw > range_from.max(range_to) {
+            return None;
+        }
+        if range_from == range_to {
+            return Some(scale_from);
+        }
+
+        // linear interpolation, rounded to the nearest thousandth
+        let numerator = (raw - range_from) * (scale_to - scale_from);
+        let denominator = range_to - range_from;
+        let (numerator, denominator) = if denominator < 0 {
+            (-numerator, -denominator)
+        } else {
+            (numerator, denominator)
+        };
+        Some(scale_from + (numerator + numerator.signum() * denominator / 2) / denominator)
+    }
+}
+
+
+/// The data fields of a profile.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct ProfileDefinition {
+    /// The profile in the form `0x00RRFFTT`.
+    pub profile: u32,
+
+    /// The number of data bytes in a telegram.
+    pub data_length: u8,
+
+    pub fields: &'static [FieldDefinition],
+}
+
+
+include!(concat!(env!("OUT_DIR"), "/eep_tables.rs"));
+
+
+/// Returns the definition of the given profile, if it is part of the tables.
+pub fn find(profile: u32) -> Option<&'static ProfileDefinition> {
+    PROFILES.binary_search_by_key(&profile, |p| p.profile)
+        .ok()
+        .map(|index| &PROFILES[index])
+}
+
+
+/// Decodes telegram data according to the profile definition.
+///
+/// Returns `None` if the data has the wrong length or the telegram is a teach-in telegram.
+pub fn decode(definition: &ProfileDefinition, data: &[u8]) -> Option<Measurements> {
+    if data.len() != usize::from(definition.data_length) {
+        // wrong format
+        return None;
+    }
+
+    let mut measurements = Measurements::default();
+    for field in definition.fields {
+        let value_milli = field.decode_milli(data);
+        match field.quantity {
+            Quantity::Temperature => {
+                measurements.temperature_tenth_celsius = value_milli
+                    .map(|v| ((v + 50 * v.signum()) / 100) as i32);
+            },
+            Quantity::RelativeHumidity => {
+                measurements.relative_humidity_half_percent = value_milli
+                    .map(|v| ((v + 250) / 500) as u8);
+            },
+            Quantity::Illumination => {
+                measurements.illumination_lux = value_milli
+                    .map(|v| ((v + 500) / 1000) as u32);
+            },
+            Quantity::Co2 => {
+                measurements.co2_ppm = value_milli
+                    .map(|v| ((v + 500) / 1000) as u16);
+            },
+            Quantity::LearnBit => {
+                if value_milli == Some(0) {
+                    // this is a teach-in packet, ignore it
+                    return None;
+                }
+            },
+        }
+    }
+    Some(measurements)
+}
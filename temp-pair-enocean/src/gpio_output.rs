@@ -76,8 +76,17 @@ pub trait GpioOutput {
 
 make_gpio_output!(BlinkyLedA8, A, 8);
 make_gpio_output!(BlinkyLedC8, C, 8);
+#[cfg(not(feature = "direct-spi-display"))]
 make_gpio_output!(TempDisplayBridgeNotReset, D, 11);
 make_gpio_output!(FlashNotChipSelect, E, 8);
 make_gpio_output!(FlashNotHoldOrNotReset, E, 7);
 make_gpio_output!(FlashWriteProtect, D, 12);
 make_gpio_output!(EnOceanNotReset, C, 15);
+
+// the 7-SEG Click directly on slot 3 instead of on the I2C-SPI shuttle
+#[cfg(feature = "direct-spi-display")]
+make_gpio_output!(TempDisplayChip1Latch, D, 13); // slot 3 CS
+#[cfg(feature = "direct-spi-display")]
+make_gpio_output!(TempDisplayChip2Latch, A, 4); // slot 3 AN
+#[cfg(feature = "direct-spi-display")]
+make_gpio_output!(TempDisplayBlank, D, 14); // slot 3 PWM
@@ -4,12 +4,111 @@
 //! scanner.
 
 
+use bitflags::bitflags;
 use stm32f7::stm32f745::Peripherals;
 
 use crate::i2c::{I2c, I2cAddress};
+use crate::marquee::ScrollPosition;
 
 
-// 3x5 hex font
+/// The row of the LED matrix whose LEDs have a dedicated meaning.
+pub const INDICATOR_ROW: usize = 7;
+
+
+bitflags! {
+    /// The LEDs in [`INDICATOR_ROW`] that have a dedicated meaning.
+    #[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+    pub struct Indicators : u8 {
+        /// The window recommendation is unknown because a temperature is missing or stale.
+        const VENTILATION_UNKNOWN = 0b0000_0001;
+
+        /// Outside air would not bring the room closer to the comfort temperature.
+        const KEEP_WINDOW_CLOSED = 0b0000_0010;
+
+        /// Outside air would bring the room closer to the comfort temperature.
+        const OPEN_WINDOW = 0b0000_0100;
+
+        /// A window handle or contact sensor reports that the window is open.
+        const WINDOW_OPEN = 0b0000_1000;
+
+        /// The CO2 concentration has exceeded the alert threshold.
+        const VENTILATE_NOW = 0b0001_0000;
+
+        /// A window handle reports that the window is tilted.
+        const WINDOW_TILTED = 0b0010_0000;
+    }
+}
+
+
+/// The row of the LED matrix showing the temperature trends.
+pub const TREND_ROW: usize = 6;
+
+
+bitflags! {
+    /// The LEDs in [`TREND_ROW`].
+    #[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+    pub struct TrendIndicators : u8 {
+        const OUTSIDE_FALLING = 0b0000_0001;
+        const OUTSIDE_STEADY = 0b0000_0010;
+        const OUTSIDE_RISING = 0b0000_0100;
+        const INSIDE_FALLING = 0b0001_0000;
+        const INSIDE_STEADY = 0b0010_0000;
+        const INSIDE_RISING = 0b0100_0000;
+    }
+}
+
+
+/// How often to ask the AS1115 whether its LED test has finished before giving up.
+const LED_TEST_POLLS: u32 = 1024;
+
+
+/// The LED tests the AS1115 can run on the LED matrix.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum LedTest {
+    Open,
+    Short,
+}
+
+
+/// The results of an LED test of the AS1115.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct LedTestResult {
+    /// The faulty LEDs, one byte per row of the LED matrix like the display data.
+    pub faulty_rows: [u8; 8],
+
+    /// The resistor setting the segment current is missing.
+    pub rset_open: bool,
+
+    /// The resistor setting the segment current is shorted.
+    pub rset_short: bool,
+}
+impl LedTestResult {
+    pub fn is_ok(&self) -> bool {
+        self.faulty_rows.iter().all(|row| *row == 0) && !self.rset_open && !self.rset_short
+    }
+}
+
+
+/// The number of rows and columns of the LED matrix.
+pub const MATRIX_SIZE: usize = 8;
+
+/// The width of a character of the font, in columns.
+const CHARACTER_WIDTH: usize = 3;
+
+/// The height of a character of the font, in rows.
+const CHARACTER_HEIGHT: usize = 5;
+
+/// The width a character takes up in a text, including the blank column after it.
+const CHARACTER_ADVANCE: usize = CHARACTER_WIDTH + 1;
+
+
+const FONT_CHARACTERS_SORTED: [char; 42] = [
+    ' ', '-', '.', '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', ':', '?',
+    'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I', 'J', 'K', 'L', 'M',
+    'N', 'O', 'P', 'Q', 'R', 'S', 'T', 'U', 'V', 'W', 'X', 'Y', 'Z', '_',
+];
+
+// 3x5 font
 // leave 1 column of pixels between chars
 //
 // a character
@@ -23,8 +122,12 @@ use crate::i2c::{I2c, I2cAddress};
 // is encoded as a 16-bit value as
 //
 // 0abc_defg_hijk_lmno
-#[allow(unused)]
-const FONT: [u16; 16] = [
+//
+// same order as FONT_CHARACTERS_SORTED
+const FONT: [u16; 42] = [
+    0b000_000_000_000_000, // space
+    0b000_000_111_000_000, // -
+    0b000_000_000_000_010, // .
     0b010_101_101_101_010, // 0
     0b001_011_001_001_001, // 1
     0b110_001_010_100_111, // 2
@@ -35,15 +138,159 @@ const FONT: [u16; 16] = [
     0b111_001_001_001_001, // 7
     0b111_101_111_101_111, // 8
     0b111_101_111_001_111, // 9
+    0b000_010_000_010_000, // :
+    0b110_001_010_000_010, // ?
     0b111_101_111_101_101, // A
     0b110_101_110_101_110, // B
     0b011_100_100_100_011, // C
     0b110_101_101_101_110, // D
     0b111_100_110_100_111, // E
     0b111_100_110_100_100, // F
+    0b011_100_101_101_011, // G
+    0b101_101_111_101_101, // H
+    0b111_010_010_010_111, // I
+    0b001_001_001_101_010, // J
+    0b101_101_110_101_101, // K
+    0b100_100_100_100_111, // L
+    0b101_111_111_101_101, // M
+    0b110_101_101_101_101, // N
+    0b111_101_101_101_111, // O (square, unlike the 0)
+    0b110_101_110_100_100, // P
+    0b010_101_101_110_011, // Q
+    0b110_101_110_101_101, // R
+    0b011_100_010_001_110, // S
+    0b111_010_010_010_010, // T
+    0b101_101_101_101_111, // U
+    0b101_101_101_101_010, // V
+    0b101_101_111_111_101, // W
+    0b101_101_010_101_101, // X
+    0b101_101_010_010_010, // Y
+    0b111_001_010_100_111, // Z
+    0b000_000_000_000_111, // _
 ];
 
 
+/// Returns the pixels of a character in the font (see [`FONT`]), or `None` if the font lacks it.
+///
+/// Lowercase letters are shown as uppercase ones.
+fn font_glyph(character: char) -> Option<u16> {
+    let index = FONT_CHARACTERS_SORTED.binary_search(&character)
+        .or_else(|_| FONT_CHARACTERS_SORTED.binary_search(&character.to_ascii_uppercase()))
+        .ok()?;
+    Some(FONT[index])
+}
+
+/// Returns the width of a text in the font, in columns.
+pub fn text_width(text: &str) -> usize {
+    (text.chars().count() * CHARACTER_ADVANCE).saturating_sub(1)
+}
+
+
+/// The contents of the LED matrix.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Framebuffer {
+    /// One byte per row, as passed to [`HmiDisplay::write_to_display`]; the most significant bit
+    /// is the leftmost column.
+    pub rows: [u8; MATRIX_SIZE],
+}
+impl Framebuffer {
+    pub const fn new() -> Self {
+        Self {
+            rows: [0u8; MATRIX_SIZE],
+        }
+    }
+
+    /// Turns off all LEDs in the given rows.
+    pub fn clear_rows(&mut self, rows: core::ops::Range<usize>) {
+        for row in &mut self.rows[rows] {
+            *row = 0;
+        }
+    }
+
+    /// Lights up the pixels of a character with its top left corner at the given row and column.
+    ///
+    /// The column may lie partially or completely outside the matrix, in which case only the
+    /// visible part of the character is drawn. Returns whether the font has the character; if
+    /// not, nothing is drawn.
+    pub fn draw_character(&mut self, character: char, row: usize, column: isize) -> bool {
+        let Some(glyph) = font_glyph(character) else {
+            return false;
+        };
+        for glyph_row in 0..CHARACTER_HEIGHT {
+            let Some(matrix_row) = self.rows.get_mut(row + glyph_row) else {
+                break;
+            };
+            for glyph_column in 0..CHARACTER_WIDTH {
+                let pixel_index = glyph_row * CHARACTER_WIDTH + glyph_column;
+                let lit = glyph & (1 << (CHARACTER_WIDTH * CHARACTER_HEIGHT - 1 - pixel_index)) != 0;
+                let matrix_column = column + glyph_column as isize;
+                if lit && (0..MATRIX_SIZE as isize).contains(&matrix_column) {
+                    *matrix_row |= 0x80 >> matrix_column;
+                }
+            }
+        }
+        true
+    }
+
+    /// Lights up the pixels of a text with its top left corner at the given row and column.
+    ///
+    /// Characters missing from the font are left blank.
+    pub fn draw_text(&mut self, text: &str, row: usize, column: isize) {
+        for (index, character) in text.chars().enumerate() {
+            let character_column = column + (index * CHARACTER_ADVANCE) as isize;
+            if character_column >= MATRIX_SIZE as isize {
+                break;
+            }
+            self.draw_character(character, row, character_column);
+        }
+    }
+}
+
+
+/// Scrolls text across the LED matrix, one column at a time.
+///
+/// The step is given per character, as for a [`Marquee`](crate::marquee::Marquee); the columns of
+/// a character share it equally.
+pub struct MatrixMarquee {
+    /// The scrolling position, in columns.
+    position: ScrollPosition,
+}
+impl MatrixMarquee {
+    /// Creates a marquee that moves the text by one character every `step_millis` milliseconds;
+    /// `counter` is the current value of the SysTick counter.
+    pub const fn new(step_millis: u32, counter: u32) -> Self {
+        Self {
+            position: ScrollPosition::new(step_millis / CHARACTER_ADVANCE as u32, counter),
+        }
+    }
+
+    pub fn set_step_millis(&mut self, step_millis: u32) {
+        self.position.set_step_millis(step_millis / CHARACTER_ADVANCE as u32);
+    }
+
+    /// Moves the text along according to the time that has passed and shows it in the rows of the
+    /// framebuffer starting at `row`.
+    ///
+    /// A text that fits onto the matrix is shown as-is; a longer one scrolls to the left and is
+    /// repeated, separated by a blank as wide as the matrix. Never blocks.
+    pub fn show(&mut self, counter: u32, text: &str, framebuffer: &mut Framebuffer, row: usize) {
+        // the text, followed by the gap, followed by the text, and so on
+        let width = text_width(text);
+        let cycle_length = if width <= MATRIX_SIZE { 0 } else { width + MATRIX_SIZE };
+        let offset = self.position.advance(counter, cycle_length);
+
+        framebuffer.clear_rows(row.min(MATRIX_SIZE)..(row + CHARACTER_HEIGHT).min(MATRIX_SIZE));
+        if cycle_length == 0 {
+            framebuffer.draw_text(text, row, 0);
+        } else {
+            let column = -(offset as isize);
+            framebuffer.draw_text(text, row, column);
+            framebuffer.draw_text(text, row, column + cycle_length as isize);
+        }
+    }
+}
+
+
 #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct HmiDisplay {
     pub i2c_address: I2cAddress,
@@ -84,6 +331,23 @@ impl HmiDisplay {
         );
     }
 
+    /// Turns off the LED matrix (or turns it back on) without changing its contents.
+    pub fn set_shutdown<I: I2c>(&self, peripherals: &Peripherals, shutdown: bool) {
+        let normal_operation: u8 = if shutdown { 0 } else { 1 };
+        I::write_data(
+            peripherals,
+            self.i2c_address,
+            &[
+                0x0C, // shutdown
+                (
+                    (0b1 << 7) // keep the feature register as it is
+                    | (0b000000 << 1) // don't-care bits
+                    | (normal_operation << 0) // shut down or operate normally
+                ),
+            ],
+        );
+    }
+
     pub fn write_to_display<I: I2c>(&self, peripherals: &Peripherals, data: &[u8]) {
         assert!(data.len() <= 8);
         let mut final_data = [0u8; 9];
@@ -96,6 +360,62 @@ impl HmiDisplay {
         );
     }
 
+    /// Runs one of the AS1115's LED tests and returns its results, or `None` if the test does not
+    /// finish.
+    ///
+    /// Blocks for the few milliseconds the test takes. The contents of the LED matrix are kept.
+    pub fn run_led_test<I: I2c>(&self, peripherals: &Peripherals, test: LedTest) -> Option<LedTestResult> {
+        let (led_short, led_open): (u8, u8) = match test {
+            LedTest::Open => (0, 1),
+            LedTest::Short => (1, 0),
+        };
+        I::write_data(
+            peripherals,
+            self.i2c_address,
+            &[
+                0x0F, // display test mode
+                (
+                    (0b00000 << 3) // read-only bits
+                    | (led_open << 2) // start the test for open LEDs
+                    | (led_short << 1) // start the test for shorted LEDs
+                    | (0b0 << 0) // do not light up all LEDs
+                ),
+            ],
+        );
+
+        let mut test_mode = [0u8; 1];
+        let mut finished = false;
+        for _ in 0..LED_TEST_POLLS {
+            I::write_then_read_data(
+                peripherals,
+                self.i2c_address,
+                &[0x0F], // display test mode
+                &mut test_mode,
+            );
+            // bit 3 stays set while the test is running
+            if test_mode[0] & (1 << 3) == 0 {
+                finished = true;
+                break;
+            }
+        }
+        if !finished {
+            return None;
+        }
+
+        let mut result = LedTestResult::default();
+        I::write_then_read_data(
+            peripherals,
+            self.i2c_address,
+            &[
+                0x14, // diagnostic of first row (automatically increments after each byte)
+            ],
+            &mut result.faulty_rows,
+        );
+        result.rset_open = test_mode[0] & (1 << 5) != 0;
+        result.rset_short = test_mode[0] & (1 << 6) != 0;
+        Some(result)
+    }
+
     pub fn read_buttons<I: I2c>(&self, peripherals: &Peripherals) -> [u8; 2] {
         let mut ret = [0u8; 2];
         I::write_data(
@@ -0,0 +1,127 @@
+//! Lighting up every LED in turn so that dead ones stand out.
+//!
+//! The lamp test first walks each segment of each digit of both seven-segment displays, then
+//! lights each digit completely, and finally walks the rows and the columns of the LED matrix. It
+//! only keeps track of time; the main loop asks it which step to show and overrides the contents
+//! of the displays with it.
+
+
+use tpe_seven_segment::{SegmentCombo, TempDisplayState};
+
+use crate::hmi_display::MATRIX_SIZE;
+
+
+/// How long each step of the lamp test is shown, in milliseconds.
+const STEP_MILLIS: u32 = 150;
+
+/// The number of seven-segment displays.
+const DISPLAYS: usize = 2;
+
+/// The number of digits on each seven-segment display.
+const DIGITS_PER_DISPLAY: usize = 3;
+
+/// The segments of a digit in the order in which they are lit.
+const SEGMENT_ORDER: [SegmentCombo; 8] = [
+    SegmentCombo::TOP,
+    SegmentCombo::TOP_RIGHT,
+    SegmentCombo::BOTTOM_RIGHT,
+    SegmentCombo::BOTTOM,
+    SegmentCombo::BOTTOM_LEFT,
+    SegmentCombo::TOP_LEFT,
+    SegmentCombo::MIDDLE,
+    SegmentCombo::DECIMAL_POINT,
+];
+
+const SEGMENT_STEPS: usize = DISPLAYS * DIGITS_PER_DISPLAY * SEGMENT_ORDER.len();
+const DIGIT_STEPS: usize = DISPLAYS * DIGITS_PER_DISPLAY;
+const MATRIX_STEPS: usize = 2 * MATRIX_SIZE;
+
+
+/// What a single step of the lamp test lights up; everything else is off.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum LampTestStep {
+    /// A single segment of a digit; display 0 is the top display.
+    Segment { display: usize, position: usize, segment: SegmentCombo },
+
+    /// All segments of a digit, including the decimal point.
+    Digit { display: usize, position: usize },
+
+    /// A row of the LED matrix.
+    MatrixRow(usize),
+
+    /// A column of the LED matrix.
+    MatrixColumn(usize),
+}
+impl LampTestStep {
+    /// Returns the step with the given index, or `None` once the lamp test is over.
+    fn from_index(index: usize) -> Option<Self> {
+        if index < SEGMENT_STEPS {
+            let digit = index / SEGMENT_ORDER.len();
+            Some(Self::Segment {
+                display: digit / DIGITS_PER_DISPLAY,
+                position: digit % DIGITS_PER_DISPLAY,
+                segment: SEGMENT_ORDER[index % SEGMENT_ORDER.len()],
+            })
+        } else if index < SEGMENT_STEPS + DIGIT_STEPS {
+            let digit = index - SEGMENT_STEPS;
+            Some(Self::Digit {
+                display: digit / DIGITS_PER_DISPLAY,
+                position: digit % DIGITS_PER_DISPLAY,
+            })
+        } else if index < SEGMENT_STEPS + DIGIT_STEPS + MATRIX_STEPS {
+            let line = index - SEGMENT_STEPS - DIGIT_STEPS;
+            if line < MATRIX_SIZE {
+                Some(Self::MatrixRow(line))
+            } else {
+                Some(Self::MatrixColumn(line - MATRIX_SIZE))
+            }
+        } else {
+            None
+        }
+    }
+
+    /// Shows the step on the seven-segment displays (top first) and in the rows of the LED matrix.
+    pub fn show(&self, displays: [&mut TempDisplayState; DISPLAYS], matrix_rows: &mut [u8; MATRIX_SIZE]) {
+        for (display_index, display) in displays.into_iter().enumerate() {
+            for position in 0..DIGITS_PER_DISPLAY {
+                let segments = match *self {
+                    Self::Segment { display, position: p, segment } if display == display_index && p == position
+                        => segment,
+                    Self::Digit { display, position: p } if display == display_index && p == position
+                        => SegmentCombo::all(),
+                    _ => SegmentCombo::empty(),
+                };
+                display.set_segments(position, segments);
+            }
+        }
+
+        for (row_index, row) in matrix_rows.iter_mut().enumerate() {
+            *row = match *self {
+                Self::MatrixRow(r) if r == row_index => 0xFF,
+                Self::MatrixColumn(c) => 1 << c,
+                _ => 0x00,
+            };
+        }
+    }
+}
+
+
+pub struct LampTest {
+    /// The value of the SysTick counter at which the lamp test started.
+    started_at: u32,
+}
+impl LampTest {
+    /// Starts a lamp test; `counter` is the current value of the SysTick counter.
+    pub const fn new(counter: u32) -> Self {
+        Self {
+            started_at: counter,
+        }
+    }
+
+    /// Returns the step to show at the given value of the SysTick counter, or `None` once the lamp
+    /// test is over.
+    pub fn step(&self, counter: u32) -> Option<LampTestStep> {
+        let index = counter.wrapping_sub(self.started_at) / STEP_MILLIS;
+        LampTestStep::from_index(index.try_into().ok()?)
+    }
+}
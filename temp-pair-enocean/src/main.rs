@@ -3,18 +3,32 @@
 
 
 mod ambient_sensor;
+mod brightness;
+mod console;
 mod crc8;
+mod display_format;
+mod eep;
 mod enocean;
 mod flash;
+mod generic_eep;
 mod gpio_output;
 mod i2c;
+mod lamp_test;
+mod marquee;
 mod hmi_display;
+mod night_mode;
+mod sensor_slot;
+mod settings;
 mod spi;
 mod systick;
 mod temp_display;
+mod trend;
 mod uart;
+mod ventilation;
+mod wall_clock;
 
 
+use core::fmt::Write;
 use core::panic::PanicInfo;
 use core::time::Duration;
 
@@ -22,18 +36,40 @@ use cortex_m_rt::entry;
 use critical_section::Mutex;
 use stm32f7::stm32f745::{Interrupt, interrupt, Peripherals};
 use stm32f7::stm32f745::spi1::cr1::BR;
+use tpe_seven_segment::{Brightness, SegmentCombo, SevenSegmentDisplay, TempDisplayState};
 use vcell::VolatileCell;
 
-use crate::ambient_sensor::AmbientLightSensor;
+use crate::ambient_sensor::{AmbientLightSensor, AutoRangingAmbientLightSensor};
+use crate::brightness::{BrightnessCurve, BrightnessFilter};
+use crate::console::{Console, ConsoleWriter};
+use crate::display_format::DisplayText;
+use crate::eep::{ContactState, DecodeError, Measurements, RockerAction, RockerButton, WindowState};
 use crate::gpio_output::{
     BlinkyLedA8, BlinkyLedC8, EnOceanNotReset, FlashNotChipSelect, FlashNotHoldOrNotReset,
-    FlashWriteProtect, GpioOutput, TempDisplayBridgeNotReset,
+    FlashWriteProtect, GpioOutput,
+};
+#[cfg(not(feature = "direct-spi-display"))]
+use crate::gpio_output::TempDisplayBridgeNotReset;
+#[cfg(feature = "direct-spi-display")]
+use crate::gpio_output::{TempDisplayBlank, TempDisplayChip1Latch, TempDisplayChip2Latch};
+use crate::hmi_display::{
+    Framebuffer, HmiDisplay, INDICATOR_ROW, Indicators, LedTest, MatrixMarquee, TREND_ROW, TrendIndicators,
 };
-use crate::hmi_display::HmiDisplay;
 use crate::i2c::{I2c, I2c2, I2cAddress};
+use crate::lamp_test::LampTest;
+use crate::marquee::{Marquee, MessageBuffer};
+use crate::night_mode::{NightMode, NightSchedule};
+use crate::sensor_slot::{Calibration, SENSOR_SLOTS_ADDRESS, SensorSlots};
+use crate::settings::{SETTINGS_ADDRESS, SettingError, Settings};
 use crate::spi::{Spi, Spi1, SpiMode};
-use crate::temp_display::{Brightness, I2cSpiBridgedTempDisplays, TempDisplayState};
+#[cfg(not(feature = "direct-spi-display"))]
+use crate::temp_display::{BridgedTlc5947Display, I2cSpiBridgedTempDisplays, Latch};
+#[cfg(feature = "direct-spi-display")]
+use crate::temp_display::SpiTlc5947Display;
+use crate::trend::{TemperatureHistory, Trend};
 use crate::uart::{Uart, Usart2, Usart3};
+use crate::ventilation::Advice;
+use crate::wall_clock::WallClock;
 
 
 pub const CLOCK_SPEED_HZ: u32 = 25_000_000;
@@ -43,19 +79,14 @@ const HMI_DISPLAY: HmiDisplay = HmiDisplay {
     // 0x00 is actually the broadcast address, but AMS was kinda stupid
     i2c_address: I2cAddress::new(0x00).unwrap(),
 };
-const TEMP_DISPLAYS: I2cSpiBridgedTempDisplays = I2cSpiBridgedTempDisplays {
-    // I2C-SPI mikroBUS board
-    i2c_spi_bridge_address: I2cAddress::new(0b0101000).unwrap(),
-    i2c_extender_address: I2cAddress::new(0b1110000).unwrap(),
-
-    // all CS pins on the I2C-SPI bridge are set to GPIO
-    // but according to the datasheet we can't pass 0, so pass 1
-    chip_select_pattern: 0b001,
-};
 const AMBIENT_LIGHT_SENSOR: AmbientLightSensor = AmbientLightSensor {
     i2c_address: I2cAddress::new(0b0101001).unwrap(),
 };
 
+/// After how many consecutive telegrams of an unexpected type a sensor that still has fresh
+/// readings is considered faulty.
+const UNEXPECTED_TELEGRAMS_BEFORE_FAULT: u8 = 3;
+
 
 #[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
 enum ButtonStatus {
@@ -69,6 +100,135 @@ impl ButtonStatus {
 
 static BUTTON_STATUS: Mutex<VolatileCell<ButtonStatus>> = Mutex::new(VolatileCell::new(ButtonStatus::Idle));
 
+/// Whether the ambient light sensor has raised an interrupt since it was last read.
+static AMBIENT_LIGHT_INTERRUPTED: Mutex<VolatileCell<bool>> = Mutex::new(VolatileCell::new(false));
+
+
+/// A measured value along with the time at which it was received.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+struct Reading<T> {
+    value: T,
+    received_at: u32,
+}
+impl<T: Copy> Reading<T> {
+    pub fn new_now(value: T) -> Self {
+        Self {
+            value,
+            received_at: crate::systick::get_counter(),
+        }
+    }
+
+    /// Returns the value, unless it is older than the given number of seconds.
+    pub fn fresh_value(&self, stale_after_seconds: u16) -> Option<T> {
+        let age_millis = crate::systick::millis_since(self.received_at);
+        if age_millis > u32::from(stale_after_seconds) * 1000 {
+            None
+        } else {
+            Some(self.value)
+        }
+    }
+}
+
+
+/// Everything we know about one of the configured sensors.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+struct SensorState {
+    temperature_tenth_celsius: Option<Reading<i32>>,
+    co2_ppm: Option<Reading<u16>>,
+    illumination_lux: Option<Reading<u32>>,
+    measurements: Measurements,
+
+    /// Why the most recent telegram of the sensor could not be decoded, if it could not.
+    fault: Option<SensorFault>,
+
+    /// The number of telegrams of an unexpected type received since the last decoded one.
+    unexpected_telegrams: u8,
+
+    /// Whether a telegram of the sensor has been decoded, whatever it contained.
+    heard_from: bool,
+}
+impl SensorState {
+    pub fn update(&mut self, measurements: &Measurements) {
+        self.fault = None;
+        self.unexpected_telegrams = 0;
+        self.heard_from = true;
+        if let Some(tenth_celsius) = measurements.temperature_tenth_celsius {
+            self.temperature_tenth_celsius = Some(Reading::new_now(tenth_celsius));
+        }
+        if let Some(ppm) = measurements.co2_ppm {
+            self.co2_ppm = Some(Reading::new_now(ppm));
+        }
+        if let Some(lux) = measurements.illumination_lux {
+            self.illumination_lux = Some(Reading::new_now(lux));
+        }
+        self.measurements.update_from(measurements);
+    }
+
+    pub fn fresh_tenth_celsius(&self, stale_after_seconds: u16) -> Option<i32> {
+        self.temperature_tenth_celsius
+            .and_then(|t| t.fresh_value(stale_after_seconds))
+    }
+
+    pub fn fresh_co2_ppm(&self, stale_after_seconds: u16) -> Option<u16> {
+        self.co2_ppm
+            .and_then(|c| c.fresh_value(stale_after_seconds))
+    }
+
+    pub fn fresh_illumination_lux(&self, stale_after_seconds: u16) -> Option<u32> {
+        self.illumination_lux
+            .and_then(|i| i.fresh_value(stale_after_seconds))
+    }
+
+    pub fn has_fresh_reading(&self, stale_after_seconds: u16) -> bool {
+        self.fresh_tenth_celsius(stale_after_seconds).is_some()
+            || self.fresh_co2_ppm(stale_after_seconds).is_some()
+            || self.fresh_illumination_lux(stale_after_seconds).is_some()
+    }
+
+    /// Records a telegram whose type does not match the configured profile. A single one is
+    /// tolerated as long as the readings of the sensor are fresh (it may well be a telegram meant
+    /// for a different role of the same sender); only repeated ones are reported as a fault.
+    pub fn record_unexpected_telegram(&mut self, stale_after_seconds: u16) {
+        self.unexpected_telegrams = self.unexpected_telegrams.saturating_add(1);
+        if self.unexpected_telegrams >= UNEXPECTED_TELEGRAMS_BEFORE_FAULT || !self.has_fresh_reading(stale_after_seconds) {
+            self.fault = Some(SensorFault::UnexpectedTelegram);
+        }
+    }
+
+    /// Returns the window state reported by a window handle or, failing that, a contact.
+    pub fn window_state(&self) -> Option<WindowState> {
+        if let Some(window_handle) = self.measurements.window_handle {
+            return Some(window_handle);
+        }
+        match self.measurements.contact {
+            Some(ContactState::Open) => Some(WindowState::Open),
+            Some(ContactState::Closed) => Some(WindowState::Closed),
+            None => None,
+        }
+    }
+}
+
+
+/// A reason why telegrams of a sensor cannot be decoded.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+enum SensorFault {
+    /// The telegram type does not match the configured profile.
+    UnexpectedTelegram,
+
+    /// The configured profile is not supported.
+    UnknownFormat,
+}
+
+
+/// The configured sensors.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+struct Sensors {
+    outside: SensorState,
+    inside: SensorState,
+    window: SensorState,
+    occupancy: SensorState,
+}
+
 
 #[panic_handler]
 fn handle_panic(_info: &PanicInfo) -> ! {
@@ -142,6 +302,35 @@ fn yield_for_flash(peripherals: &Peripherals) {
 }
 
 
+/// Erases the 4 KiB sector at the given address and writes the given data to its beginning.
+fn write_flash_sector(peripherals: &Peripherals, address: crate::flash::Address, data: &[u8]) {
+    // pull ~{write-prot} high
+    FlashWriteProtect::set_high(peripherals);
+    // enable writing
+    do_with_flash_chip_selected(peripherals, |p|
+        crate::flash::enable_writing(p)
+    );
+    // start erasing the sector
+    do_with_flash_chip_selected(peripherals, |p|
+        crate::flash::start_erase_4_kibibytes(p, address)
+    );
+    // wait until erasing is done
+    yield_for_flash(peripherals);
+    // enable writing again
+    do_with_flash_chip_selected(peripherals, |p|
+        crate::flash::enable_writing(p)
+    );
+    // write at location
+    do_with_flash_chip_selected(peripherals, |p|
+        crate::flash::write(p, address, data)
+    );
+    // wait until writing is done
+    yield_for_flash(peripherals);
+    // pull ~{write-prot} low
+    FlashWriteProtect::set_low(peripherals);
+}
+
+
 /// Reconfigures the clocks of the microcontroller.
 ///
 /// By default, the clocks are set up as follows:
@@ -349,6 +538,7 @@ fn setup_pins(peripherals: &mut Peripherals) {
         .pupdr14().pull_up() // datasheet says AS1115 leaves this floating or pulls to GND
     );
     peripherals.GPIOD.pupdr().modify(|_, w| w
+        .pupdr10().pull_up() // VEML4031X00 interrupt output is open-drain
         .pupdr13().floating() // not used (Click ID for light sensor board)
     );
 
@@ -371,6 +561,7 @@ fn setup_pins(peripherals: &mut Peripherals) {
     peripherals.GPIOD.moder().modify(|_, w| w
         .moder8().alternate() // USART3
         .moder9().alternate() // USART3
+        .moder10().input() // ambient light interrupt
         .moder11().output() // I2C-SPI bridge reset
         .moder12().output() // flash write protection
     );
@@ -412,6 +603,71 @@ const fn divide_u32_to_u16_round(dividend: u32, divisor: u32) -> u16 {
 }
 
 
+/// What is shown on the temperature displays while no setup is in progress.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+enum DisplayMode {
+    /// The outside temperature on top, the inside temperature on the bottom.
+    #[default] Temperatures,
+
+    /// The CO2 concentration (in ppm) across both displays.
+    Co2,
+
+    /// The comfort temperature on top, the inside temperature on the bottom.
+    Comfort,
+
+    /// The time and date of the wall clock, scrolling across both displays.
+    Clock,
+
+    /// The illumination (in lux) measured by the outside sensor across both displays.
+    OutdoorLight,
+}
+
+
+impl DisplayMode {
+    pub fn next(&self) -> Self {
+        match self {
+            Self::Temperatures => Self::Co2,
+            Self::Co2 => Self::Comfort,
+            Self::Comfort => Self::Clock,
+            Self::Clock => Self::OutdoorLight,
+            Self::OutdoorLight => Self::Temperatures,
+        }
+    }
+
+    pub fn previous(&self) -> Self {
+        match self {
+            Self::Temperatures => Self::OutdoorLight,
+            Self::Co2 => Self::Temperatures,
+            Self::Comfort => Self::Co2,
+            Self::Clock => Self::Comfort,
+            Self::OutdoorLight => Self::Clock,
+        }
+    }
+}
+
+
+/// User input, regardless of where it came from.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+enum InputEvent {
+    /// Keys have been pressed on the 8800 Retro (one bit per key).
+    Keys(u16),
+
+    /// A button on the rocker switch has been pressed or released.
+    Rocker(RockerAction),
+
+    /// The occupancy sensor has detected somebody.
+    Presence,
+}
+
+
+/// Something a console command asks the main loop to do.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+enum ConsoleRequest {
+    /// Start a lamp test.
+    LampTest,
+}
+
+
 #[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
 enum AppState {
     #[default] Idle,
@@ -486,18 +742,42 @@ fn main() -> ! {
     BlinkyLedC8::set_up(&peripherals);
     BlinkyLedC8::turn_on(&peripherals);
 
-    // reset the I2C-SPI bridge and expander for the 7-seg displays
-    TempDisplayBridgeNotReset::set_low(&peripherals);
-    for _ in 0..1024 {
-        cortex_m::asm::nop();
-    }
-    TempDisplayBridgeNotReset::set_high(&peripherals);
-    for _ in 0..1024 {
-        cortex_m::asm::nop();
-    }
+    #[cfg(not(feature = "direct-spi-display"))]
+    let temp_displays = {
+        // I2C-SPI mikroBUS board
+        let temp_displays = I2cSpiBridgedTempDisplays::new(
+            I2cAddress::new(0b0101000).unwrap(),
+            I2cAddress::new(0b1110000).unwrap(),
 
-    // set up the I2C-SPI bridge and expander for the 7-seg displays
-    TEMP_DISPLAYS.set_up::<I2c2>(&peripherals);
+            // all CS pins on the I2C-SPI bridge are set to GPIO
+            // but according to the datasheet we can't pass 0, so pass 1
+            0b001,
+        );
+
+        // reset the I2C-SPI bridge and expander for the 7-seg displays
+        TempDisplayBridgeNotReset::set_low(&peripherals);
+        for _ in 0..1024 {
+            cortex_m::asm::nop();
+        }
+        TempDisplayBridgeNotReset::set_high(&peripherals);
+        for _ in 0..1024 {
+            cortex_m::asm::nop();
+        }
+
+        // set up the I2C-SPI bridge and expander for the 7-seg displays
+        temp_displays.set_up::<I2c2>(&peripherals);
+        temp_displays
+    };
+    #[cfg(feature = "direct-spi-display")]
+    {
+        // no latching and no blanking of the 7-seg displays yet
+        TempDisplayChip1Latch::set_up(&peripherals);
+        TempDisplayChip1Latch::set_low(&peripherals);
+        TempDisplayChip2Latch::set_up(&peripherals);
+        TempDisplayChip2Latch::set_low(&peripherals);
+        TempDisplayBlank::set_up(&peripherals);
+        TempDisplayBlank::set_low(&peripherals);
+    }
 
     // configure the ambient light sensor
     AMBIENT_LIGHT_SENSOR.set_up::<I2c2>(&peripherals);
@@ -564,15 +844,10 @@ fn main() -> ! {
         ],
     );
 
-    // read outside and inside address and packet format from flash
-    let mut address_buffer = [
-        0, 0, 0, 0, // outside address
-        0, 0, 0, // outside packet format
-        0, 0, 0, 0, // inside address
-        0, 0, 0, // inside packet format
-    ];
+    // read outside and inside address, packet format and calibration from flash
+    let mut address_buffer = [0u8; SensorSlots::SERIALIZED_LENGTH];
     do_with_flash_chip_selected(&peripherals, |p|
-        crate::flash::read(p, crate::flash::Address::new(0).unwrap(), &mut address_buffer)
+        crate::flash::read(p, SENSOR_SLOTS_ADDRESS, &mut address_buffer)
     );
     // visualize what is programmed into Flash
     HMI_DISPLAY.write_to_display::<I2c2>(
@@ -580,24 +855,14 @@ fn main() -> ! {
         &address_buffer[0..8],
     );
 
-    let mut outside_address =
-        u32::from(address_buffer[0]) << 24
-        | u32::from(address_buffer[1]) << 16
-        | u32::from(address_buffer[2]) <<  8
-        | u32::from(address_buffer[3]) <<  0;
-    let mut outside_format =
-        u32::from(address_buffer[4]) << 16
-        | u32::from(address_buffer[5]) <<  8
-        | u32::from(address_buffer[6]) <<  0;
-    let mut inside_address =
-        u32::from(address_buffer[7]) << 24
-        | u32::from(address_buffer[8]) << 16
-        | u32::from(address_buffer[9]) <<  8
-        | u32::from(address_buffer[10]) <<  0;
-    let mut inside_format =
-        u32::from(address_buffer[11]) << 16
-        | u32::from(address_buffer[12]) <<  8
-        | u32::from(address_buffer[13]) <<  0;
+    let mut sensor_slots = SensorSlots::from_bytes(&address_buffer);
+
+    // read the settings from flash
+    let mut settings_buffer = [0u8; Settings::SERIALIZED_LENGTH];
+    do_with_flash_chip_selected(&peripherals, |p|
+        crate::flash::read(p, SETTINGS_ADDRESS, &mut settings_buffer)
+    );
+    let mut settings = Settings::from_bytes(&settings_buffer);
 
     // reset EnOcean module
     EnOceanNotReset::set_low(&peripherals);
@@ -608,28 +873,45 @@ fn main() -> ! {
 
     let mut top_display = TempDisplayState::new(true);
     let mut bottom_display = TempDisplayState::new(false);
+    #[cfg(not(feature = "direct-spi-display"))]
+    let (mut top_transport, mut bottom_transport) = (
+        BridgedTlc5947Display::<I2c2>::new(&temp_displays, Latch::Chip1, slot_3_interrupt_asserted),
+        BridgedTlc5947Display::<I2c2>::new(&temp_displays, Latch::Chip2, slot_3_interrupt_asserted),
+    );
+    #[cfg(feature = "direct-spi-display")]
+    let (mut top_transport, mut bottom_transport) = (
+        SpiTlc5947Display::<TempDisplayChip1Latch>::new(),
+        SpiTlc5947Display::<TempDisplayChip2Latch>::new(),
+    );
 
     // set the brightness to full by default
     let fullbright = Brightness::new(0x0FFF).unwrap();
     top_display.set_brightness(fullbright);
     bottom_display.set_brightness(fullbright);
 
-    update_displays(&peripherals, &mut top_display, &mut bottom_display, true);
+    update_displays(&peripherals, &mut top_display, &mut top_transport, &mut bottom_display, &mut bottom_transport, true);
 
-    // allow 8800 to interrupt us (PB14)
+    // allow 8800 (PB14) and the ambient light sensor (PD10) to interrupt us
     peripherals.EXTI.rtsr().modify(|_, w| w
-        .tr14().disabled() // do not trigger on rising signal
+        .tr10().disabled() // do not trigger on rising signal
+        .tr14().disabled()
     );
     peripherals.EXTI.ftsr().modify(|_, w| w
-        .tr14().enabled() // trigger on falling signal
+        .tr10().enabled() // trigger on falling signal
+        .tr14().enabled()
     );
     peripherals.EXTI.imr().modify(|_, w| w
-        .mr14().unmasked() // turn it on
+        .mr10().unmasked() // turn it on
+        .mr14().unmasked()
     );
     peripherals.EXTI.pr().write(|w| w
-        .pr14().clear_bit_by_one() // clear any pending interrupt
+        .pr10().clear_bit_by_one() // clear any pending interrupt
+        .pr14().clear_bit_by_one()
+    );
+    // link external interrupt 10 with GPIOD and 14 with GPIOB
+    peripherals.SYSCFG.exticr3().modify(|_, w| w
+        .exti10().pd()
     );
-    // link external interrupt 14 with GPIOB
     peripherals.SYSCFG.exticr4().modify(|_, w| w
         .exti14().pb()
     );
@@ -648,34 +930,196 @@ fn main() -> ! {
 
     let mut app_state = AppState::Idle;
     let mut new_setup_nibbles: [u8; 28] = [0; 28];
+    let mut sensors = Sensors::default();
+    let mut wall_clock = WallClock::new();
+    let mut outside_history = TemperatureHistory::new();
+    let mut inside_history = TemperatureHistory::new();
+    let mut advice = Advice::Unknown;
+    let mut co2_alert = false;
+    let mut co2_alert_acknowledged = false;
+    let mut display_mode = DisplayMode::Temperatures;
+    let mut clock_marquee = Marquee::new(settings.marquee_step_millis.into(), crate::systick::get_counter());
+    let mut status_marquee = MatrixMarquee::new(settings.marquee_step_millis.into(), crate::systick::get_counter());
+    let mut displays_off = false;
+    let mut last_presence_at = Some(crate::systick::get_counter());
+    let mut displays_blanked = false;
+    let mut ambient_light = AutoRangingAmbientLightSensor::new(AMBIENT_LIGHT_SENSOR, crate::systick::get_counter());
+    // start out at full brightness, like the displays
+    let mut brightness_filter = BrightnessFilter::new(fullbright.as_u16(), crate::systick::get_counter());
+    let mut night_mode = NightMode::new();
+    let mut hmi_shut_down = false;
+    let mut console = Console::new();
+
+    // check all the LEDs once at boot
+    report_led_matrix_faults(&peripherals);
+    let mut lamp_test = Some(LampTest::new(crate::systick::get_counter()));
     loop {
         // EnOcean logic
         let packet_result = crate::enocean::process_one_packet(&peripherals);
-        act_upon_one_packet(
+        let radio_event = act_upon_one_packet(
+            &peripherals,
             packet_result,
-            outside_address, outside_format,
-            inside_address, inside_format,
-            &mut top_display,
-            &mut bottom_display,
+            &sensor_slots,
+            &settings,
+            &mut sensors,
+            &mut wall_clock,
+        );
+        wall_clock.advance(crate::systick::get_counter());
+
+        // ventilation logic
+        let comfort_tenth_celsius = comfort_tenth_celsius(&settings, &sensors);
+        advice = crate::ventilation::advise(
+            advice,
+            sensors.outside.fresh_tenth_celsius(settings.stale_after_seconds),
+            sensors.inside.fresh_tenth_celsius(settings.stale_after_seconds),
+            comfort_tenth_celsius,
+            settings.comfort_hysteresis_tenth_celsius.into(),
         );
+        let mut indicators = match advice {
+            Advice::Unknown => Indicators::VENTILATION_UNKNOWN,
+            Advice::OpenWindow => Indicators::OPEN_WINDOW,
+            Advice::KeepClosed => Indicators::KEEP_WINDOW_CLOSED,
+        };
+        let window_state = [&sensors.window, &sensors.outside, &sensors.inside]
+            .into_iter()
+            .filter_map(|s| s.window_state())
+            .max(); // prefer Open over Tilted over Closed
+        match window_state {
+            Some(WindowState::Open) => indicators |= Indicators::WINDOW_OPEN,
+            Some(WindowState::Tilted) => indicators |= Indicators::WINDOW_TILTED,
+            Some(WindowState::Closed) => {},
+            None => {},
+        }
+
+        // is the window letting in warmer air?
+        let warm_air_incoming = match window_state {
+            Some(WindowState::Open|WindowState::Tilted) => {
+                let outside_tenth_celsius = sensors.outside.fresh_tenth_celsius(settings.stale_after_seconds);
+                let inside_tenth_celsius = sensors.inside.fresh_tenth_celsius(settings.stale_after_seconds);
+                match (outside_tenth_celsius, inside_tenth_celsius) {
+                    (Some(o), Some(i)) => o > i,
+                    _ => false,
+                }
+            },
+            _ => false,
+        };
+
+        // trend logic
+        let now = crate::systick::get_counter();
+        outside_history.record(sensors.outside.fresh_tenth_celsius(settings.stale_after_seconds), now);
+        inside_history.record(sensors.inside.fresh_tenth_celsius(settings.stale_after_seconds), now);
+        let mut trend_indicators = TrendIndicators::empty();
+        let trends = [
+            (&outside_history, TrendIndicators::OUTSIDE_FALLING, TrendIndicators::OUTSIDE_STEADY, TrendIndicators::OUTSIDE_RISING),
+            (&inside_history, TrendIndicators::INSIDE_FALLING, TrendIndicators::INSIDE_STEADY, TrendIndicators::INSIDE_RISING),
+        ];
+        for (history, falling, steady, rising) in trends {
+            let trend = history.trend(settings.trend_window_minutes, settings.trend_threshold_tenth_celsius, now);
+            match trend {
+                Some(Trend::Falling) => trend_indicators |= falling,
+                Some(Trend::Steady) => trend_indicators |= steady,
+                Some(Trend::Rising) => trend_indicators |= rising,
+                None => {},
+            }
+        }
+
+        // air quality logic
+        let co2_ppm = highest_co2_ppm(&sensors.outside, &sensors.inside, settings.stale_after_seconds);
+        co2_alert = match co2_ppm {
+            Some(ppm) => if co2_alert {
+                ppm > settings.co2_clear_ppm
+            } else {
+                ppm >= settings.co2_alert_ppm
+            },
+            None => false,
+        };
+        if !co2_alert {
+            // the next alert must be acknowledged anew
+            co2_alert_acknowledged = false;
+        }
+        if co2_alert && !co2_alert_acknowledged {
+            indicators |= Indicators::VENTILATE_NOW;
+        }
+
+        // console logic
+        if let Some(line) = console.poll_line(&peripherals) {
+            let request = handle_console_command(
+                &peripherals,
+                line,
+                &mut settings,
+                &mut sensor_slots,
+                &mut display_mode,
+                &sensors,
+                &wall_clock,
+            );
+            if request == Some(ConsoleRequest::LampTest) {
+                report_led_matrix_faults(&peripherals);
+                lamp_test = Some(LampTest::new(crate::systick::get_counter()));
+            }
+        }
+
+        // lamp test logic
+        let lamp_test_step = lamp_test.as_ref()
+            .and_then(|test| test.step(crate::systick::get_counter()));
+        if lamp_test_step.is_none() {
+            lamp_test = None;
+        }
 
         // process background tasks
         yield_for(&peripherals, Duration::ZERO);
 
         // ambient light logic
-        let brightness_u16 = AMBIENT_LIGHT_SENSOR.read_ambient_light::<I2c2>(&peripherals);
-        // FIXME: scaling factor? curve?
-        let mut brightness_u12 = Brightness::new(brightness_u16 >> 4).unwrap();
-        if brightness_u12.as_u16() == 0 {
-            brightness_u12 = Brightness::new(1).unwrap();
-        }
+        let ambient_light_interrupted = critical_section::with(|cs| {
+            let cell = AMBIENT_LIGHT_INTERRUPTED.borrow(cs);
+            let interrupted = cell.get();
+            cell.set(false);
+            interrupted
+        });
+        let ambient_millilux = ambient_light.read_millilux::<I2c2>(
+            &peripherals,
+            ambient_light_interrupted,
+            crate::systick::get_counter(),
+        );
+        let ambient_lux = ambient_millilux / 1000;
+        let brightness_curve = BrightnessCurve {
+            full_lux: settings.brightness_full_lux,
+            gamma_tenths: settings.brightness_gamma_tenths,
+            min_level: settings.brightness_min,
+            max_level: settings.brightness_max,
+        };
+        let brightness_level = brightness_filter.update(
+            brightness_curve.level(ambient_millilux),
+            settings.brightness_smoothing_millis.into(),
+            settings.brightness_hysteresis_percent,
+            crate::systick::get_counter(),
+        );
+        let brightness_u12 = Brightness::new(brightness_level).unwrap();
         top_display.set_brightness(brightness_u12);
         bottom_display.set_brightness(brightness_u12);
 
-        // DEBUG: output raw value to HMI display
-        let mut hmi_display_bytes = [0u8; 8];
-        hmi_display_bytes[0..2].copy_from_slice(&brightness_u16.to_be_bytes());
-        HMI_DISPLAY.write_to_display::<I2c2>(&peripherals, &hmi_display_bytes);
+        // segment intensities and transitions
+        for display in [&mut top_display, &mut bottom_display] {
+            for position in 0..3 {
+                display.set_intensity(position, SegmentCombo::DECIMAL_POINT, settings.decimal_point_intensity_percent);
+            }
+            // the lamp test steps from one LED to the next without fading
+            let fade_millis = if lamp_test_step.is_some() { 0 } else { settings.fade_millis.into() };
+            display.set_fade_millis(fade_millis);
+            display.advance_fades(crate::systick::get_counter());
+        }
+
+        // sensor problems scroll across the top of the HMI display
+        let mut hmi_framebuffer = Framebuffer::new();
+        let mut status: MessageBuffer<64> = MessageBuffer::new();
+        let _ = write_matrix_status(&sensor_slots, &sensors, &mut status);
+        status_marquee.set_step_millis(settings.marquee_step_millis.into());
+        status_marquee.show(crate::systick::get_counter(), status.as_str(), &mut hmi_framebuffer, 0);
+        hmi_framebuffer.rows[TREND_ROW] = trend_indicators.bits();
+        hmi_framebuffer.rows[INDICATOR_ROW] = indicators.bits();
+        if let Some(step) = &lamp_test_step {
+            step.show([&mut top_display, &mut bottom_display], &mut hmi_framebuffer.rows);
+        }
+        HMI_DISPLAY.write_to_display::<I2c2>(&peripherals, &hmi_framebuffer.rows);
 
         // process background tasks
         yield_for(&peripherals, Duration::ZERO);
@@ -692,158 +1136,253 @@ fn main() -> ! {
                 },
             }
         });
-        if all_key_values != 0 {
-            // popcount
-            let pop_count = all_key_values.count_ones();
-            debug_assert_ne!(pop_count, 0);
-            if pop_count > 1 {
-                // multiple buttons pressed; go back to idle
-                app_state = AppState::Idle;
-            } else {
-                // a single button; now we have to make hard decisions
-                let value: u8 = match all_key_values {
-                    0x0001 => 0,
-                    0x0002 => 1,
-                    0x0004 => 2,
-                    0x0008 => 3,
-                    0x0010 => 4,
-                    0x0020 => 5,
-                    0x0040 => 6,
-                    0x0080 => 7,
-                    0x0100 => 8,
-                    0x0200 => 9,
-                    0x0400 => 10,
-                    0x0800 => 11,
-                    0x1000 => 12,
-                    0x2000 => 13,
-                    0x4000 => 14,
-                    0x8000 => 15,
-                    _ => unreachable!(), // popcount would not be 1
-                };
-
-                match app_state {
-                    AppState::Idle => {
-                        // the first byte of a new setup
-                        new_setup_nibbles.fill(0);
-                        new_setup_nibbles[0] = value;
-                    },
-                    AppState::NewSetup(i) => {
-                        // a subsequent byte
-                        new_setup_nibbles[i] = value;
-                    },
-                }
+        let key_event = if all_key_values != 0 {
+            Some(InputEvent::Keys(all_key_values))
+        } else {
+            None
+        };
+        for input_event in [key_event, radio_event].into_iter().flatten() {
+            // any input means that somebody is here
+            last_presence_at = Some(crate::systick::get_counter());
+
+            // both upper rocker buttons together start or end the night
+            let both_upper = matches!(
+                input_event,
+                InputEvent::Rocker(action) if action.is_both(RockerButton::AOn, RockerButton::BOn)
+            );
+            if both_upper {
+                night_mode.toggle();
+                continue;
+            }
 
-                app_state = app_state.incremented();
-
-                match app_state {
-                    AppState::Idle => {
-                        // and now the magic happens
-
-                        // move the nibbles into the correct variables
-                        outside_address =
-                            u32::from(new_setup_nibbles[ 0]) << 28
-                            | u32::from(new_setup_nibbles[ 1]) << 24
-                            | u32::from(new_setup_nibbles[ 2]) << 20
-                            | u32::from(new_setup_nibbles[ 3]) << 16
-                            | u32::from(new_setup_nibbles[ 4]) << 12
-                            | u32::from(new_setup_nibbles[ 5]) <<  8
-                            | u32::from(new_setup_nibbles[ 6]) <<  4
-                            | u32::from(new_setup_nibbles[ 7]) <<  0;
-                        outside_format =
-                            u32::from(new_setup_nibbles[ 8]) << 20
-                            | u32::from(new_setup_nibbles[ 9]) << 16
-                            | u32::from(new_setup_nibbles[10]) << 12
-                            | u32::from(new_setup_nibbles[11]) <<  8
-                            | u32::from(new_setup_nibbles[12]) <<  4
-                            | u32::from(new_setup_nibbles[13]) <<  0;
-                        inside_address =
-                            u32::from(new_setup_nibbles[14]) << 28
-                            | u32::from(new_setup_nibbles[15]) << 24
-                            | u32::from(new_setup_nibbles[16]) << 20
-                            | u32::from(new_setup_nibbles[17]) << 16
-                            | u32::from(new_setup_nibbles[18]) << 12
-                            | u32::from(new_setup_nibbles[19]) <<  8
-                            | u32::from(new_setup_nibbles[20]) <<  4
-                            | u32::from(new_setup_nibbles[21]) <<  0;
-                        inside_format =
-                            u32::from(new_setup_nibbles[22]) << 20
-                            | u32::from(new_setup_nibbles[23]) << 16
-                            | u32::from(new_setup_nibbles[24]) << 12
-                            | u32::from(new_setup_nibbles[25]) <<  8
-                            | u32::from(new_setup_nibbles[26]) <<  4
-                            | u32::from(new_setup_nibbles[27]) <<  0;
-
-                        // erase the first block of flash
-                        // pull ~{write-prot} high
-                        FlashWriteProtect::set_high(&peripherals);
-                        // enable writing
-                        do_with_flash_chip_selected(&peripherals, |p|
-                            crate::flash::enable_writing(p)
-                        );
-                        // start erasing first 4k
-                        do_with_flash_chip_selected(&peripherals, |p|
-                            crate::flash::start_erase_4_kibibytes(p, crate::flash::Address::new(0).unwrap())
-                        );
-                        // wait until erasing is done
-                        yield_for_flash(&peripherals);
-                        // enable writing again
-                        do_with_flash_chip_selected(&peripherals, |p|
-                            crate::flash::enable_writing(p)
-                        );
-                        // prepare writing buffer
-                        let writing_buffer = [
-                            ((outside_address >> 24) & 0xFF) as u8,
-                            ((outside_address >> 16) & 0xFF) as u8,
-                            ((outside_address >>  8) & 0xFF) as u8,
-                            ((outside_address >>  0) & 0xFF) as u8,
-                            ((outside_format >> 16) & 0xFF) as u8,
-                            ((outside_format >>  8) & 0xFF) as u8,
-                            ((outside_format >>  0) & 0xFF) as u8,
-                            ((inside_address >> 24) & 0xFF) as u8,
-                            ((inside_address >> 16) & 0xFF) as u8,
-                            ((inside_address >>  8) & 0xFF) as u8,
-                            ((inside_address >>  0) & 0xFF) as u8,
-                            ((inside_format >> 16) & 0xFF) as u8,
-                            ((inside_format >>  8) & 0xFF) as u8,
-                            ((inside_format >>  0) & 0xFF) as u8,
-                        ];
-                        // write at location
-                        do_with_flash_chip_selected(&peripherals, |p|
-                            crate::flash::write(p, crate::flash::Address::new(0).unwrap(), &writing_buffer)
-                        );
-                        // wait until writing is done
-                        yield_for_flash(&peripherals);
-                        // pull ~{write-prot} low
-                        FlashWriteProtect::set_low(&peripherals);
-
-                        // now the variables are updated and the state is persisted
-
-                        // turn off the displays
-                        top_display.set_digit(0, b' ', false);
-                        top_display.set_digit(1, b' ', false);
-                        top_display.set_digit(2, b' ', false);
-                        bottom_display.set_digit(0, b' ', false);
-                        bottom_display.set_digit(1, b' ', false);
-                        bottom_display.set_digit(2, b' ', false);
-
-                        // we can go back to regular temperature processing
-                    },
-                    AppState::NewSetup(next_nibble_index) => {
-                        if next_nibble_index <= 8 {
-                            // outside address
-                            show_nibbles_starting_at(&new_setup_nibbles, 0, next_nibble_index, &mut top_display, &mut bottom_display);
-                        } else if next_nibble_index <= 14 {
-                            // outside format
-                            show_nibbles_starting_at(&new_setup_nibbles, 8, next_nibble_index, &mut top_display, &mut bottom_display);
-                        } else if next_nibble_index <= 22 {
-                            // inside address
-                            show_nibbles_starting_at(&new_setup_nibbles, 14, next_nibble_index, &mut top_display, &mut bottom_display);
-                        } else {
-                            // inside format
-                            show_nibbles_starting_at(&new_setup_nibbles, 22, next_nibble_index, &mut top_display, &mut bottom_display);
+            // at night, a keypress only wakes the displays
+            let is_keypress = matches!(input_event, InputEvent::Keys(_) | InputEvent::Rocker(RockerAction::Pressed(_)));
+            if is_keypress && night_mode.wake(crate::systick::get_counter()) {
+                continue;
+            }
+
+            match input_event {
+                InputEvent::Keys(all_key_values) => {
+                    // popcount
+                    let pop_count = all_key_values.count_ones();
+                    debug_assert_ne!(pop_count, 0);
+                    if pop_count > 1 {
+                        // multiple buttons pressed; go back to idle
+                        app_state = AppState::Idle;
+                    } else {
+                        // a single button; now we have to make hard decisions
+                        let value: u8 = match all_key_values {
+                            0x0001 => 0,
+                            0x0002 => 1,
+                            0x0004 => 2,
+                            0x0008 => 3,
+                            0x0010 => 4,
+                            0x0020 => 5,
+                            0x0040 => 6,
+                            0x0080 => 7,
+                            0x0100 => 8,
+                            0x0200 => 9,
+                            0x0400 => 10,
+                            0x0800 => 11,
+                            0x1000 => 12,
+                            0x2000 => 13,
+                            0x4000 => 14,
+                            0x8000 => 15,
+                            _ => unreachable!(), // popcount would not be 1
+                        };
+
+                        match app_state {
+                            AppState::Idle => {
+                                // the first byte of a new setup
+                                new_setup_nibbles.fill(0);
+                                new_setup_nibbles[0] = value;
+                            },
+                            AppState::NewSetup(i) => {
+                                // a subsequent byte
+                                new_setup_nibbles[i] = value;
+                            },
                         }
-                    },
-                }
+
+                        app_state = app_state.incremented();
+
+                        match app_state {
+                            AppState::Idle => {
+                                // and now the magic happens
+
+                                // move the nibbles into the correct variables
+                                sensor_slots.outside.address =
+                                    u32::from(new_setup_nibbles[ 0]) << 28
+                                    | u32::from(new_setup_nibbles[ 1]) << 24
+                                    | u32::from(new_setup_nibbles[ 2]) << 20
+                                    | u32::from(new_setup_nibbles[ 3]) << 16
+                                    | u32::from(new_setup_nibbles[ 4]) << 12
+                                    | u32::from(new_setup_nibbles[ 5]) <<  8
+                                    | u32::from(new_setup_nibbles[ 6]) <<  4
+                                    | u32::from(new_setup_nibbles[ 7]) <<  0;
+                                sensor_slots.outside.format =
+                                    u32::from(new_setup_nibbles[ 8]) << 20
+                                    | u32::from(new_setup_nibbles[ 9]) << 16
+                                    | u32::from(new_setup_nibbles[10]) << 12
+                                    | u32::from(new_setup_nibbles[11]) <<  8
+                                    | u32::from(new_setup_nibbles[12]) <<  4
+                                    | u32::from(new_setup_nibbles[13]) <<  0;
+                                sensor_slots.inside.address =
+                                    u32::from(new_setup_nibbles[14]) << 28
+                                    | u32::from(new_setup_nibbles[15]) << 24
+                                    | u32::from(new_setup_nibbles[16]) << 20
+                                    | u32::from(new_setup_nibbles[17]) << 16
+                                    | u32::from(new_setup_nibbles[18]) << 12
+                                    | u32::from(new_setup_nibbles[19]) <<  8
+                                    | u32::from(new_setup_nibbles[20]) <<  4
+                                    | u32::from(new_setup_nibbles[21]) <<  0;
+                                sensor_slots.inside.format =
+                                    u32::from(new_setup_nibbles[22]) << 20
+                                    | u32::from(new_setup_nibbles[23]) << 16
+                                    | u32::from(new_setup_nibbles[24]) << 12
+                                    | u32::from(new_setup_nibbles[25]) <<  8
+                                    | u32::from(new_setup_nibbles[26]) <<  4
+                                    | u32::from(new_setup_nibbles[27]) <<  0;
+
+                                // write into the first block of flash
+                                // (the calibration of the slots is kept)
+                                write_flash_sector(
+                                    &peripherals,
+                                    SENSOR_SLOTS_ADDRESS,
+                                    &sensor_slots.to_bytes(),
+                                );
+
+                                // now the variables are updated and the state is persisted;
+                                // the old readings belong to different sensors
+                                // (which also turns off the displays until new values arrive)
+                                sensors = Sensors::default();
+
+                                // we can go back to regular temperature processing
+                            },
+                            AppState::NewSetup(next_nibble_index) => {
+                                if next_nibble_index <= 8 {
+                                    // outside address
+                                    show_nibbles_starting_at(&new_setup_nibbles, 0, next_nibble_index, &mut top_display, &mut bottom_display);
+                                } else if next_nibble_index <= 14 {
+                                    // outside format
+                                    show_nibbles_starting_at(&new_setup_nibbles, 8, next_nibble_index, &mut top_display, &mut bottom_display);
+                                } else if next_nibble_index <= 22 {
+                                    // inside address
+                                    show_nibbles_starting_at(&new_setup_nibbles, 14, next_nibble_index, &mut top_display, &mut bottom_display);
+                                } else {
+                                    // inside format
+                                    show_nibbles_starting_at(&new_setup_nibbles, 22, next_nibble_index, &mut top_display, &mut bottom_display);
+                                }
+                            },
+                        }
+                    }
+                },
+                InputEvent::Rocker(action) => {
+                    if app_state != AppState::Idle {
+                        // any rocker action cancels the setup
+                        app_state = AppState::Idle;
+                        continue;
+                    }
+
+                    match action {
+                        RockerAction::Pressed(RockerButton::AOn) => {
+                            display_mode = display_mode.next();
+                        },
+                        RockerAction::Pressed(RockerButton::AOff) => {
+                            display_mode = display_mode.previous();
+                        },
+                        RockerAction::Pressed(RockerButton::BOn) => {
+                            displays_off = !displays_off;
+                        },
+                        RockerAction::Pressed(RockerButton::BOff) => {
+                            // acknowledge the current alert
+                            co2_alert_acknowledged = co2_alert;
+                        },
+                        _ => {},
+                    }
+                },
+                InputEvent::Presence => {},
+            }
+        }
+
+        // turn the displays off if nobody has been around for a while
+        if let Some(presence_at) = last_presence_at {
+            let absence_millis = u32::from(settings.absence_blank_seconds) * 1000;
+            if settings.absence_blank_seconds > 0 && crate::systick::millis_since(presence_at) > absence_millis {
+                last_presence_at = None;
+            }
+        }
+
+        // turn all displays off at night
+        let night_schedule = NightSchedule {
+            light_threshold_lux: settings.night_light_threshold_lux,
+            light_delay_seconds: settings.night_light_delay_seconds,
+            from_minute: settings.night_from_minute,
+            until_minute: settings.night_until_minute,
+            wake_seconds: settings.night_wake_seconds,
+        };
+        let minute_of_day = wall_clock.time()
+            .map(|time| u16::from(time.hour) * 60 + u16::from(time.minute));
+        night_mode.update(&night_schedule, ambient_lux, minute_of_day, crate::systick::get_counter());
+        // (but never during a lamp test)
+        let night = night_mode.is_blanked() && lamp_test_step.is_none();
+        if hmi_shut_down != night {
+            HMI_DISPLAY.set_shutdown::<I2c2>(&peripherals, night);
+            hmi_shut_down = night;
+        }
+
+        let absent = last_presence_at.is_none() && settings.occupancy_sensor_address != 0 && lamp_test_step.is_none();
+        let blank = absent || night;
+        if displays_blanked != blank {
+            // the blank pin is shared by both displays
+            #[cfg(not(feature = "direct-spi-display"))]
+            temp_displays.set_blank::<I2c2>(&peripherals, blank);
+            #[cfg(feature = "direct-spi-display")]
+            if blank {
+                TempDisplayBlank::set_high(&peripherals);
+            } else {
+                TempDisplayBlank::set_low(&peripherals);
+            }
+            displays_blanked = blank;
+        }
+
+        // show the values unless the setup or the lamp test (shown above) is using the displays
+        if lamp_test_step.is_some() {
+            // leave the lamp test pattern alone
+        } else if app_state == AppState::Idle && displays_off {
+            for display in [&mut top_display, &mut bottom_display] {
+                display.set_digit(0, b' ', false);
+                display.set_digit(1, b' ', false);
+                display.set_digit(2, b' ', false);
+            }
+        } else if app_state == AppState::Idle {
+            match display_mode {
+                DisplayMode::Temperatures => {
+                    // mark the outside temperature if it is currently streaming in through the window
+                    set_display_to_temperature_reading(&settings, &sensors.outside, warm_air_incoming, &mut top_display);
+                    set_display_to_temperature_reading(&settings, &sensors.inside, false, &mut bottom_display);
+                },
+                DisplayMode::Co2 => {
+                    set_displays_to_number(co2_ppm.map(u32::from), &mut top_display, &mut bottom_display);
+                },
+                DisplayMode::Comfort => {
+                    let comfort_text = DisplayText::temperature_tenths(tenths_in_display_unit(&settings, comfort_tenth_celsius));
+                    set_display_to_text(comfort_text, false, &mut top_display);
+                    set_display_to_temperature_reading(&settings, &sensors.inside, false, &mut bottom_display);
+                },
+                DisplayMode::Clock => {
+                    let mut message: MessageBuffer<24> = MessageBuffer::new();
+                    let _ = write_wall_clock_message(&wall_clock, &mut message);
+                    clock_marquee.set_step_millis(settings.marquee_step_millis.into());
+                    clock_marquee.show(
+                        crate::systick::get_counter(),
+                        message.as_str(),
+                        &mut [&mut top_display, &mut bottom_display],
+                    );
+                },
+                DisplayMode::OutdoorLight => {
+                    let outdoor_lux = sensors.outside.fresh_illumination_lux(settings.stale_after_seconds);
+                    set_displays_to_number(outdoor_lux, &mut top_display, &mut bottom_display);
+                },
             }
         }
 
@@ -854,7 +1393,9 @@ fn main() -> ! {
         update_displays(
             &peripherals,
             &mut top_display,
+            &mut top_transport,
             &mut bottom_display,
+            &mut bottom_transport,
             false,
         );
 
@@ -908,37 +1449,36 @@ fn show_nibbles_starting_at(
 }
 
 fn act_upon_one_packet(
+    peripherals: &Peripherals,
     packet_result: crate::enocean::PacketResult,
-    outside_address: u32,
-    outside_format: u32,
-    inside_address: u32,
-    inside_format: u32,
-    top_display: &mut TempDisplayState,
-    bottom_display: &mut TempDisplayState,
-) {
+    sensor_slots: &SensorSlots,
+    settings: &Settings,
+    sensors: &mut Sensors,
+    wall_clock: &mut WallClock,
+) -> Option<InputEvent> {
     // needs to be an EnOcean packet
     let (packet_type, payload) = match packet_result {
         enocean::PacketResult::Packet { packet_type, payload }
             => (packet_type, payload),
-        _ => return,
+        _ => return None,
     };
 
     // needs to be an ERP1 packet
     if packet_type != crate::enocean::PacketType::RadioErp1 {
-        return;
+        return None;
     }
 
     // must have at least 1 byte for packet type
     let payload_data = payload.data();
     if payload_data.len() < 1 {
-        return;
+        return None;
     }
 
     let (data_slice, sender) = match payload_data[0] {
         0xF6|0xD5 => {
             // one type identifier, one byte of data, four of sender, one of status
             if payload_data.len() != 7 {
-                return;
+                return None;
             }
 
             let sender = u32::from_be_bytes(payload_data[2..6].try_into().unwrap());
@@ -947,7 +1487,7 @@ fn act_upon_one_packet(
         0xA5 => {
             // one type identifier, four bytes of data, four of sender, one of status
             if payload_data.len() != 10 {
-                return;
+                return None;
             }
 
             let sender = u32::from_be_bytes(payload_data[5..9].try_into().unwrap());
@@ -958,7 +1498,7 @@ fn act_upon_one_packet(
             // (the additional CRC only shows up in the radio protocol;
             // the serial protocol does its own CRCs)
             if payload_data.len() < 6 {
-                return;
+                return None;
             }
 
             let data = &payload_data[1..payload_data.len()-5];
@@ -967,215 +1507,486 @@ fn act_upon_one_packet(
         },
         _ => {
             // some other type of radio packet, we don't care
-            return;
+            return None;
         },
     };
 
-    if sender == outside_address {
+    // a sender may fill several roles (e.g. an A5-08-xx sensor as both the inside sensor and the
+    // occupancy sensor); each profile is only decoded once
+    let mut last_decoded: Option<(u32, Result<Measurements, DecodeError>)> = None;
+    let mut decode = |profile: u32| -> Result<Measurements, DecodeError> {
+        match last_decoded {
+            Some((decoded_profile, measurements)) if decoded_profile == profile => measurements,
+            _ => {
+                let measurements = crate::eep::decode(profile, data_slice);
+                last_decoded = Some((profile, measurements));
+                measurements
+            },
+        }
+    };
+    let mut input_event = None;
+
+    if sender == settings.rocker_switch_address {
+        // rocker switches (F6-02-01/02) send RPS telegrams
+        if payload_data[0] == 0xF6 {
+            // the status byte tells us how to interpret the data byte
+            let status = payload_data[payload_data.len()-1];
+            input_event = crate::eep::decode_rocker(data_slice[0], status)
+                .map(InputEvent::Rocker);
+        }
+    }
+
+    if sender == settings.occupancy_sensor_address
+        && format_matches(settings.occupancy_sensor_profile, payload_data[0])
+        && let Ok(measurements) = decode(settings.occupancy_sensor_profile)
+    {
+        sensors.occupancy.update(&measurements);
+        if measurements.motion_detected == Some(true) || measurements.occupancy_button_pressed == Some(true) {
+            input_event = input_event.or(Some(InputEvent::Presence));
+        }
+    }
+
+    if sender == settings.window_sensor_address {
+        // window handles (F6-10-00) and contacts (D5-00-01) are distinguished by their telegram type
+        let window_format = match payload_data[0] {
+            0xF6 => Some(0xF6_10_00),
+            0xD5 => Some(0xD5_00_01),
+            _ => None,
+        };
+        if let Some(Ok(measurements)) = window_format.map(&mut decode) {
+            sensors.window.update(&measurements);
+        }
+    }
+
+    let slots = [
+        (&sensor_slots.outside, &mut sensors.outside),
+        (&sensor_slots.inside, &mut sensors.inside),
+    ];
+    let mut slot_measurements = None;
+    for (slot, sensor) in slots {
+        if sender != slot.address {
+            continue;
+        }
+
         // is the packet in the correct format?
         // ff-xx-xx
-        if !format_matches(outside_format, payload_data[0]) {
+        if !format_matches(slot.format, payload_data[0]) {
             // no, this packet is in a different format
-            return;
+            sensor.record_unexpected_telegram(settings.stale_after_seconds);
+            continue;
         }
 
-        // decode the temperature value
-        decode_temperature(outside_format, data_slice, top_display);
-    } else if sender == inside_address {
-        if !format_matches(inside_format, payload_data[0]) {
-            // no, this packet is in a different format
-            return;
+        // decode the values
+        let measurements = match decode(slot.format) {
+            Ok(measurements) => Some(slot.calibration.apply_to(measurements)),
+            Err(DecodeError::UnknownProfile) => {
+                sensor.fault = Some(SensorFault::UnknownFormat);
+                continue;
+            },
+            Err(DecodeError::InvalidTelegram) => None,
+        };
+        if let Some(measurements) = &measurements {
+            sensor.update(measurements);
+            set_wall_clock(wall_clock, measurements);
         }
+        slot_measurements = Some((slot.format, measurements));
+    }
+
+    // answer a valve once, with the comfort temperature that takes its reading into account
+    if let Some((format, measurements)) = slot_measurements {
+        let comfort_tenth_celsius = comfort_tenth_celsius(settings, sensors);
+        answer_valve(peripherals, settings, comfort_tenth_celsius, sender, format, data_slice, measurements.as_ref());
+    }
+
+    input_event
+}
 
-        // decode the temperature value
-        decode_temperature(inside_format, data_slice, bottom_display);
+/// Sets the wall clock from the date and time contained in the measurements, if any.
+fn set_wall_clock(wall_clock: &mut WallClock, measurements: &Measurements) {
+    if let Some(date) = measurements.date {
+        wall_clock.set_date(date);
+    }
+    if let Some(time) = measurements.time {
+        wall_clock.set_time(time, crate::systick::get_counter());
     }
 }
 
+/// Answers a telegram of a valve actuator (A5-20-01), if enabled in the settings.
+///
+/// The valve expects an answer to every telegram it sends; without one, it falls back to its
+/// failsafe behavior. Teach-in queries are answered as well.
+fn answer_valve(
+    peripherals: &Peripherals,
+    settings: &Settings,
+    comfort_tenth_celsius: i32,
+    sender: u32,
+    format: u32,
+    data_slice: &[u8],
+    measurements: Option<&Measurements>,
+) {
+    if format != 0xA5_20_01 || settings.valve_reply == 0 {
+        return;
+    }
+
+    let reply = match measurements {
+        Some(m) => match m.temperature_tenth_celsius {
+            Some(t) => crate::eep::valve_reply(comfort_tenth_celsius, t),
+            None => return,
+        },
+        None => match crate::eep::teach_in_response(data_slice) {
+            Some(r) => r,
+            None => return,
+        },
+    };
+    crate::enocean::send_radio_telegram(peripherals, 0xA5, &reply, sender);
+}
+
 fn format_matches(
     known_format: u32,
     packet_format: u8,
 ) -> bool {
     // known_format is ff-xx-xx
-    let expected_format = ((known_format >> 16) & 0xFF) as u8;
-    expected_format == packet_format
+    crate::eep::rorg(known_format) == packet_format
 }
 
-fn decode_temperature(
-    format: u32,
-    data_slice: &[u8],
-    display: &mut TempDisplayState,
-) {
-    if format == 0xA5_09_04 {
-        // HHHH_HHHH CCCC_CCCC TTTT_TTTT 0000_Lxx0
-        let data = match data_slice.try_into() {
-            Ok(ds) => u32::from_be_bytes(ds),
-            Err(_) => {
-                // wrong format
-                return;
-            },
-        };
-
-        if data & 0b1000 == 0 {
-            // this is a teach-in packet, ignore it
-            return;
-        }
+/// Returns the temperature we are aiming for, in units of 0.1 °C.
+///
+/// This is the setpoint of a room operating panel (preferably the inside sensor) if so configured
+/// and known; otherwise, it is the comfort temperature from the settings.
+fn comfort_tenth_celsius(settings: &Settings, sensors: &Sensors) -> i32 {
+    if settings.use_setpoint == 0 {
+        return settings.comfort_tenth_celsius.into();
+    }
 
-        // 8 bits of temperature in units of 0.2 °C
-        let temperature_bits = ((data >> 8) & 0xFF) as u16;
-        let temperature_tenth_celsius = temperature_bits * 2;
+    let setpoint_permille = sensors.inside.measurements.setpoint_permille
+        .or(sensors.outside.measurements.setpoint_permille);
+    match setpoint_permille {
+        Some(permille) => {
+            let min: i32 = settings.setpoint_min_tenth_celsius.into();
+            let max: i32 = settings.setpoint_max_tenth_celsius.into();
+            min + ((max - min) * i32::from(permille) + 500) / 1000
+        },
+        None => settings.comfort_tenth_celsius.into(),
+    }
+}
 
-        let temperature_digit_0 = if temperature_tenth_celsius >= 100 {
-            b'0' + u8::try_from(temperature_tenth_celsius / 100).unwrap()
-        } else {
-            b' '
-        };
-        // digit 1 is before the decimal point so always there even if it's zero
-        let temperature_digit_1 = b'0' + u8::try_from((temperature_tenth_celsius / 10) % 10).unwrap();
-        let temperature_digit_2 = b'0' + u8::try_from(temperature_tenth_celsius % 10).unwrap();
-
-        display.set_digit(0, temperature_digit_0, false);
-        display.set_digit(1, temperature_digit_1, true);
-        display.set_digit(2, temperature_digit_2, false);
-    } else if format == 0xA5_04_01 {
-        // 0000_0000 HHHH_HHHH TTTT_TTTT 0000_L0T0
-        let data = match data_slice.try_into() {
-            Ok(ds) => u32::from_be_bytes(ds),
-            Err(_) => {
-                // wrong format
-                return;
-            },
-        };
+/// Returns the highest CO2 concentration that either sensor recently reported.
+fn highest_co2_ppm(
+    outside_sensor: &SensorState,
+    inside_sensor: &SensorState,
+    stale_after_seconds: u16,
+) -> Option<u16> {
+    let outside_ppm = outside_sensor.fresh_co2_ppm(stale_after_seconds);
+    let inside_ppm = inside_sensor.fresh_co2_ppm(stale_after_seconds);
+    match (outside_ppm, inside_ppm) {
+        (Some(o), Some(i)) => Some(o.max(i)),
+        (Some(o), None) => Some(o),
+        (None, i) => i,
+    }
+}
 
-        if data & 0b1000 == 0 {
-            // this is a teach-in packet, ignore it
-            return;
-        }
+/// Converts a temperature in units of 0.1 °C into tenths of the unit chosen in the settings.
+fn tenths_in_display_unit(settings: &Settings, tenth_celsius: i32) -> i32 {
+    if settings.fahrenheit == 0 {
+        tenth_celsius
+    } else {
+        // F = C * 9/5 + 32, rounded to the nearest tenth
+        let scaled = tenth_celsius.saturating_mul(18);
+        scaled.saturating_add(5 * scaled.signum()) / 10 + 320
+    }
+}
 
-        // 8 bits of temperature 0..=250 from 0 to +40 °C
-        // steps of 0.16 °C; let's aim for one decimal digit
-        let temperature_bits = (data >> 8) & 0xFF;
-        let temperature_hundredth_celsius = temperature_bits * 16 / 100;
-        let temperature_tenth_celsius = i32::try_from(temperature_hundredth_celsius / 10).unwrap();
-        set_display_to_temperature_tenth_celsius(temperature_tenth_celsius, display);
-    } else if format == 0xA5_04_03 {
-        // HHHH_HHHH 0000_00TT TTTT_TTTT 0000_L00x
-        let data = match data_slice.try_into() {
-            Ok(ds) => u32::from_be_bytes(ds),
-            Err(_) => {
-                // wrong format
-                return;
-            },
-        };
+/// Shows the temperature reading of the sensor on the display in the unit chosen in the settings,
+/// or why there is none.
+///
+/// If `mark` is set, the decimal point of the last digit is lit as well.
+fn set_display_to_temperature_reading(
+    settings: &Settings,
+    sensor: &SensorState,
+    mark: bool,
+    display: &mut TempDisplayState,
+) {
+    let text = match (sensor.fault, sensor.temperature_tenth_celsius) {
+        (Some(SensorFault::UnexpectedTelegram), _) => DisplayText::ERROR,
+        (Some(SensorFault::UnknownFormat), _) => DisplayText::FORMAT_UNKNOWN,
+        (None, Some(r)) => DisplayText::temperature_tenths(tenths_in_display_unit(settings, r.value)),
+        // nothing received yet
+        (None, None) => DisplayText::BLANK,
+    };
+    set_display_to_text(text, mark, display);
+}
 
-        if data & 0b1000 == 0 {
-            // this is a teach-in packet, ignore it
-            return;
+/// Shows a number (such as the CO2 concentration) right-aligned across both displays, or dashes if
+/// it is unknown.
+///
+/// Numbers that do not fit are shown as 999999.
+fn set_displays_to_number(
+    number: Option<u32>,
+    top_display: &mut TempDisplayState,
+    bottom_display: &mut TempDisplayState,
+) {
+    let mut digits = [b'-'; 6];
+    if let Some(number) = number {
+        let mut remaining = number.min(999_999);
+        for (i, digit) in digits.iter_mut().enumerate().rev() {
+            *digit = if remaining == 0 && i < 5 {
+                // no leading zeroes (but a single zero if the value is zero)
+                b' '
+            } else {
+                b'0' + u8::try_from(remaining % 10).unwrap()
+            };
+            remaining /= 10;
         }
+    }
 
-        // 10 bits of temperature from -20 to +60 °C
-        // let's aim for a single decimal digit
-        let temperature_bits = (data >> 8) & 0x3FF;
-        let temperature_tenth_celsius = ((temperature_bits * 800) / 1024) as i32 - 200;
-        set_display_to_temperature_tenth_celsius(temperature_tenth_celsius, display);
-    } else if format == 0xD2_14_41 {
-        // TTTT_TTTT TTHH_HHHH HHII_IIII IIII_IIII IIIA_AXXX XXXX_XXXY YYYY_YYYY YZZZ_ZZZZ ZZZC_0000
-        if data_slice.len() != 9 {
-            // wrong format
-            return;
-        }
+    top_display.set_digit(0, digits[0], false);
+    top_display.set_digit(1, digits[1], false);
+    top_display.set_digit(2, digits[2], false);
+    bottom_display.set_digit(0, digits[3], false);
+    bottom_display.set_digit(1, digits[4], false);
+    bottom_display.set_digit(2, digits[5], false);
+}
 
-        let temperature_bits =
-            ((u16::from(data_slice[0]) & 0b1111_1111) << 2)
-            | ((u16::from(data_slice[1]) & 0b1100_0000) >> 6);
+/// Writes the time and date of the wall clock in a form suitable for the seven-segment displays.
+fn write_wall_clock_message<W: Write>(wall_clock: &WallClock, writer: &mut W) -> core::fmt::Result {
+    let Some(time) = wall_clock.time() else {
+        return write!(writer, "--.--.--");
+    };
+    write!(writer, "{:02}.{:02}.{:02}", time.hour, time.minute, time.second)?;
+    if let Some(date) = wall_clock.date() {
+        write!(writer, "  {:02}.{:02}.{:04}", date.day, date.month, date.year)?;
+    }
+    Ok(())
+}
 
-        // 10 bits of temperature mapping [0; 1000] to [-40 °C; +60 °C]
-        // pretty easy to derive tenths of degrees
-        let temperature_tenth_celsius = i32::from(temperature_bits) - 400;
-        set_display_to_temperature_tenth_celsius(temperature_tenth_celsius, display);
-    } else {
-        // don't know how to decode this format
+/// Writes what is wrong with the outside and inside sensors for the HMI display: the sender ID of
+/// a sensor that has not been heard from yet, followed by `?`; `ERR` and the sender ID of a sensor
+/// whose telegrams do not match its profile; `FMT` and the profile of a sensor whose profile is not
+/// supported. Slots without a sensor (address 0) are skipped. Writes nothing if all sensors are
+/// fine.
+fn write_matrix_status<W: Write>(sensor_slots: &SensorSlots, sensors: &Sensors, writer: &mut W) -> core::fmt::Result {
+    let mut separator = "";
+    let slots = [
+        ("OUT", &sensor_slots.outside, &sensors.outside),
+        ("IN", &sensor_slots.inside, &sensors.inside),
+    ];
+    for (name, slot, sensor) in slots {
+        if slot.address == 0 {
+            // not configured
+            continue;
+        }
+        match (sensor.fault, sensor.heard_from) {
+            (Some(SensorFault::UnexpectedTelegram), _) => write!(writer, "{}{} ERR {:08X}", separator, name, slot.address)?,
+            (Some(SensorFault::UnknownFormat), _) => write!(writer, "{}{} FMT {:06X}", separator, name, slot.format)?,
+            (None, true) => continue,
+            (None, false) => write!(writer, "{}{} {:08X}?", separator, name, slot.address)?,
+        }
+        separator = "  ";
     }
+    Ok(())
 }
 
-fn set_display_to_temperature_tenth_celsius(
-    temperature_tenth_celsius: i32,
+/// Shows the text on the display.
+///
+/// If `mark` is set, the decimal point of the last digit is lit as well.
+fn set_display_to_text(
+    text: DisplayText,
+    mark: bool,
     display: &mut TempDisplayState,
 ) {
-    if temperature_tenth_celsius <= -100 {
-        // t <= -10.0 °C
-        // show as -TT
-        let abs_temp = (-temperature_tenth_celsius) / 10;
-        let temperature_digit_0 = b'-';
-        let temperature_digit_1 = b'0' + u8::try_from(abs_temp / 10).unwrap();
-        let temperature_digit_2 = b'0' + u8::try_from(abs_temp % 10).unwrap();
-        display.set_digit(0, temperature_digit_0, false);
-        display.set_digit(1, temperature_digit_1, false);
-        display.set_digit(2, temperature_digit_2, false);
-    } else if temperature_tenth_celsius < 0 {
-        // -10.0 °C < t < 0.0 °C
-        // show as -T.T
-        let abs_temp = -temperature_tenth_celsius;
-        let temperature_digit_0 = b'-';
-        let temperature_digit_1 = b'0' + u8::try_from(abs_temp / 10).unwrap();
-        let temperature_digit_2 = b'0' + u8::try_from(abs_temp % 10).unwrap();
-        display.set_digit(0, temperature_digit_0, false);
-        display.set_digit(1, temperature_digit_1, true);
-        display.set_digit(2, temperature_digit_2, false);
-    } else if temperature_tenth_celsius < 100 {
-        // 0.0 °C <= t < 10.0 °C
-        // show as T.T
-        let temperature_digit_0 = b' ';
-        let temperature_digit_1 = b'0' + u8::try_from(temperature_tenth_celsius / 10).unwrap();
-        let temperature_digit_2 = b'0' + u8::try_from(temperature_tenth_celsius % 10).unwrap();
-        display.set_digit(0, temperature_digit_0, false);
-        display.set_digit(1, temperature_digit_1, true);
-        display.set_digit(2, temperature_digit_2, false);
-    } else {
-        // t >= 10.0 °C
-        // show as TT.T
-        let temperature_digit_0 = b'0' + u8::try_from(temperature_tenth_celsius / 100).unwrap();
-        let temperature_digit_1 = b'0' + u8::try_from((temperature_tenth_celsius / 10) % 10).unwrap();
-        let temperature_digit_2 = b'0' + u8::try_from(temperature_tenth_celsius % 10).unwrap();
-        display.set_digit(0, temperature_digit_0, false);
-        display.set_digit(1, temperature_digit_1, true);
-        display.set_digit(2, temperature_digit_2, false);
-    }
+    display.set_digit(0, text.characters[0], text.decimal_points[0]);
+    display.set_digit(1, text.characters[1], text.decimal_points[1]);
+    display.set_digit(2, text.characters[2], text.decimal_points[2] || mark);
 }
 
-fn update_displays(
+fn update_displays<T: SevenSegmentDisplay<Context = Peripherals>, B: SevenSegmentDisplay<Context = Peripherals>>(
     peripherals: &Peripherals,
     top_display: &mut TempDisplayState,
+    top_transport: &mut T,
     bottom_display: &mut TempDisplayState,
+    bottom_transport: &mut B,
     force: bool,
 ) {
-    if force || top_display.is_dirty() {
-        // send top display data via I2C/SPI
-        top_display.send_via_i2c_spi_bridge::<I2c2>(
-            peripherals,
-            &TEMP_DISPLAYS,
-            true,
-        );
-        // pull the chip 1 XLAT pin up, wait a bit, then pull it down again
-        TEMP_DISPLAYS.set_chip_1_xlat::<I2c2>(peripherals, true);
-        for _ in 0..1024 {
-            cortex_m::asm::nop();
-        }
-        TEMP_DISPLAYS.set_chip_1_xlat::<I2c2>(peripherals, false);
+    // the I2C-SPI bridge signals the end of each transfer on the interrupt line it shares with the
+    // ambient light sensor; keep that from looking like a change in ambient light
+    peripherals.EXTI.imr().modify(|_, w| w
+        .mr10().masked()
+    );
+
+    top_transport.update(peripherals, top_display, force);
+    bottom_transport.update(peripherals, bottom_display, force);
+
+    peripherals.EXTI.pr().write(|w| w
+        .pr10().clear_bit_by_one()
+    );
+    peripherals.EXTI.imr().modify(|_, w| w
+        .mr10().unmasked()
+    );
+    if slot_3_interrupt_asserted(peripherals) {
+        // the bridge has released the line, so this is the ambient light sensor
+        critical_section::with(|cs| {
+            AMBIENT_LIGHT_INTERRUPTED.borrow(cs).set(true);
+        });
     }
+}
 
-    if force || bottom_display.is_dirty() {
-        // same for the bottom display
-        bottom_display.send_via_i2c_spi_bridge::<I2c2>(
-            peripherals,
-            &TEMP_DISPLAYS,
-            true,
-        );
-        TEMP_DISPLAYS.set_chip_2_xlat::<I2c2>(peripherals, true);
-        for _ in 0..1024 {
-            cortex_m::asm::nop();
+/// Returns whether the interrupt line of mikroBUS slot 3 (PD10) is pulled low, either by the
+/// I2C-SPI bridge or by the ambient light sensor.
+fn slot_3_interrupt_asserted(peripherals: &Peripherals) -> bool {
+    peripherals.GPIOD.idr().read().idr10().is_low()
+}
+
+fn handle_console_command(
+    peripherals: &Peripherals,
+    line: &str,
+    settings: &mut Settings,
+    sensor_slots: &mut SensorSlots,
+    display_mode: &mut DisplayMode,
+    sensors: &Sensors,
+    wall_clock: &WallClock,
+) -> Option<ConsoleRequest> {
+    let mut writer = ConsoleWriter::new(peripherals);
+    let mut pieces = line.split(' ').filter(|p| !p.is_empty());
+    let command = match pieces.next() {
+        Some(c) => c,
+        None => return None,
+    };
+
+    match (command, pieces.next(), pieces.next(), pieces.next()) {
+        ("list", None, None, None) => {
+            for name in Settings::NAMES {
+                let _ = write!(writer, "{} = {}\r\n", name, settings.get(name).unwrap());
+            }
+        },
+        ("get", Some(name), None, None) => {
+            match settings.get(name) {
+                Some(value) => { let _ = write!(writer, "{} = {}\r\n", name, value); },
+                None => { let _ = write!(writer, "unknown setting\r\n"); },
+            }
+        },
+        ("set", Some(name), Some(value_str), None) => {
+            let value = match crate::console::parse_i64(value_str) {
+                Some(v) => v,
+                None => {
+                    let _ = write!(writer, "invalid number\r\n");
+                    return None;
+                },
+            };
+            match settings.set(name, value) {
+                Ok(()) => { let _ = write!(writer, "{} = {}\r\n", name, value); },
+                Err(SettingError::UnknownName) => { let _ = write!(writer, "unknown setting\r\n"); },
+                Err(SettingError::OutOfRange) => { let _ = write!(writer, "value out of range\r\n"); },
+                Err(SettingError::Conflict) => { let _ = write!(writer, "value conflicts with another setting\r\n"); },
+            }
+        },
+        ("calibration", None, None, None) => {
+            for (name, slot) in [("outside", &sensor_slots.outside), ("inside", &sensor_slots.inside)] {
+                let _ = write!(
+                    writer, "{}: offset {} gain {}\r\n",
+                    name, slot.calibration.offset_tenth_celsius, slot.calibration.gain_permille,
+                );
+            }
+        },
+        ("calibrate", Some(name), Some(offset_str), gain_str) => {
+            let slot = match name {
+                "outside" => &mut sensor_slots.outside,
+                "inside" => &mut sensor_slots.inside,
+                _ => {
+                    let _ = write!(writer, "unknown sensor\r\n");
+                    return None;
+                },
+            };
+            let offset = crate::console::parse_i64(offset_str);
+            let gain = match gain_str {
+                Some(g) => crate::console::parse_i64(g),
+                None => Some(1000),
+            };
+            let (Some(offset), Some(gain)) = (offset, gain) else {
+                let _ = write!(writer, "invalid number\r\n");
+                return None;
+            };
+            let calibration = i16::try_from(offset).ok()
+                .zip(u16::try_from(gain).ok())
+                .and_then(|(offset, gain)| Calibration::new(offset, gain));
+            match calibration {
+                Some(c) => {
+                    slot.calibration = c;
+                    let _ = write!(writer, "{}: offset {} gain {}\r\n", name, offset, gain);
+                },
+                None => { let _ = write!(writer, "value out of range\r\n"); },
+            }
+        },
+        ("display", Some("temperatures"), None, None) => {
+            *display_mode = DisplayMode::Temperatures;
+        },
+        ("display", Some("co2"), None, None) => {
+            *display_mode = DisplayMode::Co2;
+        },
+        ("display", Some("comfort"), None, None) => {
+            *display_mode = DisplayMode::Comfort;
+        },
+        ("display", Some("clock"), None, None) => {
+            *display_mode = DisplayMode::Clock;
+        },
+        ("display", Some("light"), None, None) => {
+            *display_mode = DisplayMode::OutdoorLight;
+        },
+        ("status", None, None, None) => {
+            let _ = write!(writer, "outside:{}\r\n", sensors.outside.measurements);
+            let _ = write!(writer, "inside:{}\r\n", sensors.inside.measurements);
+            let _ = write!(writer, "window:{}\r\n", sensors.window.measurements);
+            let _ = write!(writer, "occupancy:{}\r\n", sensors.occupancy.measurements);
+            match (wall_clock.date(), wall_clock.time()) {
+                (Some(date), Some(time)) => { let _ = write!(writer, "clock: {} {}\r\n", date, time); },
+                (None, Some(time)) => { let _ = write!(writer, "clock: {}\r\n", time); },
+                _ => { let _ = write!(writer, "clock: unknown\r\n"); },
+            }
+        },
+        ("save", None, None, None) => {
+            write_flash_sector(peripherals, SETTINGS_ADDRESS, &settings.to_bytes());
+            write_flash_sector(peripherals, SENSOR_SLOTS_ADDRESS, &sensor_slots.to_bytes());
+            let _ = write!(writer, "saved\r\n");
+        },
+        ("lamptest", None, None, None) => {
+            return Some(ConsoleRequest::LampTest);
+        },
+        _ => {
+            let _ = write!(writer, "commands: status, display temperatures|co2|comfort|clock|light, list, get NAME, set NAME VALUE, calibration, calibrate outside|inside OFFSET [GAIN], lamptest, save\r\n");
+        },
+    }
+    None
+}
+
+/// Runs the LED tests of the LED matrix and reports any faulty LEDs on the console.
+///
+/// The TLC5947s driving the seven-segment displays cannot report faulty LEDs; those have to be
+/// watched during the lamp test.
+fn report_led_matrix_faults(peripherals: &Peripherals) {
+    let mut writer = ConsoleWriter::new(peripherals);
+    let mut all_ok = true;
+    for (test, fault) in [(LedTest::Open, "open"), (LedTest::Short, "shorted")] {
+        let Some(result) = HMI_DISPLAY.run_led_test::<I2c2>(peripherals, test) else {
+            let _ = write!(writer, "lamp test: LED matrix {} test did not finish\r\n", fault);
+            all_ok = false;
+            continue;
+        };
+        for (row, faulty_row) in result.faulty_rows.iter().enumerate() {
+            for column in 0..8 {
+                if faulty_row & (1 << column) != 0 {
+                    let _ = write!(writer, "lamp test: LED matrix row {} column {} {}\r\n", row, column, fault);
+                }
+            }
+        }
+        if result.rset_open {
+            let _ = write!(writer, "lamp test: LED matrix current resistor open\r\n");
+        }
+        if result.rset_short {
+            let _ = write!(writer, "lamp test: LED matrix current resistor shorted\r\n");
         }
-        TEMP_DISPLAYS.set_chip_2_xlat::<I2c2>(peripherals, false);
+        all_ok &= result.is_ok();
+    }
+    if all_ok {
+        let _ = write!(writer, "lamp test: no faulty LEDs in the LED matrix\r\n");
     }
 }
 
@@ -1218,4 +2029,15 @@ fn EXTI15_10() {
                 .set(ButtonStatus::ReadRequested);
         });
     }
+    if peripherals.EXTI.pr().read().pr10().is_pending() {
+        // the ambient light has left the threshold window
+
+        // clear the pending bit
+        peripherals.EXTI.pr().write(|w| w.pr10().clear_bit_by_one());
+
+        // mark for the main loop
+        critical_section::with(|cs| {
+            AMBIENT_LIGHT_INTERRUPTED.borrow(cs).set(true);
+        });
+    }
 }
@@ -0,0 +1,178 @@
+//! Scrolling text on the seven-segment displays.
+//!
+//! A marquee scrolls a message across one or more three-digit displays: either both displays as
+//! one six-digit display or each display as an independent three-digit lane with a marquee of its
+//! own. The marquee only keeps track of the scrolling position; the message is passed anew every
+//! time it is shown, so it may change while it is being scrolled.
+
+
+use core::fmt;
+
+use tpe_seven_segment::{glyph, SegmentCombo, TempDisplayState};
+
+
+/// The number of digits on each display.
+const DIGITS_PER_DISPLAY: usize = 3;
+
+/// The maximum number of displays a marquee can span.
+const MAX_DISPLAYS: usize = 2;
+
+
+/// How far scrolling content has moved, advancing by one step every so many milliseconds.
+pub struct ScrollPosition {
+    /// How long each step takes, in milliseconds.
+    step_millis: u32,
+
+    /// How many steps the content has been moved to the left.
+    offset: usize,
+
+    /// The value of the SysTick counter at which the last step was taken.
+    last_step_at: u32,
+}
+impl ScrollPosition {
+    /// Starts at the beginning of the content; `counter` is the current value of the SysTick
+    /// counter.
+    pub const fn new(step_millis: u32, counter: u32) -> Self {
+        Self {
+            step_millis,
+            offset: 0,
+            last_step_at: counter,
+        }
+    }
+
+    pub fn set_step_millis(&mut self, step_millis: u32) {
+        self.step_millis = step_millis;
+    }
+
+    /// Takes the steps that are due at the given value of the SysTick counter and returns the new
+    /// offset, which starts over after `cycle_length` steps; a cycle length of 0 keeps the content
+    /// at its beginning.
+    pub fn advance(&mut self, counter: u32, cycle_length: usize) -> usize {
+        let step_millis = self.step_millis.max(1);
+        let steps = counter.wrapping_sub(self.last_step_at) / step_millis;
+        self.last_step_at = self.last_step_at.wrapping_add(steps * step_millis);
+        self.offset = (self.offset + steps as usize) % cycle_length.max(1);
+        self.offset
+    }
+}
+
+
+pub struct Marquee {
+    position: ScrollPosition,
+}
+impl Marquee {
+    /// Creates a marquee that moves the message by one character every `step_millis` milliseconds;
+    /// `counter` is the current value of the SysTick counter.
+    pub const fn new(step_millis: u32, counter: u32) -> Self {
+        Self {
+            position: ScrollPosition::new(step_millis, counter),
+        }
+    }
+
+    pub fn set_step_millis(&mut self, step_millis: u32) {
+        self.position.set_step_millis(step_millis);
+    }
+
+    /// Moves the message along according to the time that has passed and shows it on the
+    /// displays (at most two), which are treated as one display.
+    ///
+    /// A message that fits onto the displays is shown as-is; a longer one enters from the right
+    /// and is repeated, separated by as many blanks as the displays have digits. A period lights
+    /// up the decimal point of the preceding character. Never blocks.
+    pub fn show(&mut self, counter: u32, message: &str, displays: &mut [&mut TempDisplayState]) {
+        assert!(displays.len() <= MAX_DISPLAYS);
+        let width = displays.len() * DIGITS_PER_DISPLAY;
+        let message_length = message.chars()
+            .filter(|c| *c != '.')
+            .count();
+
+        // the message, followed by the gap, followed by the message, and so on
+        let cycle_length = if message_length <= width { 0 } else { message_length + width };
+        let offset = self.position.advance(counter, cycle_length);
+
+        let mut segments = [SegmentCombo::empty(); MAX_DISPLAYS * DIGITS_PER_DISPLAY];
+        let segments = &mut segments[..width];
+        if cycle_length == 0 {
+            render(message.chars(), segments);
+        } else {
+            let mut characters = message.chars()
+                .chain(core::iter::repeat_n(' ', width))
+                .cycle()
+                .peekable();
+            let mut skipped = 0;
+            while skipped < offset {
+                if characters.next() != Some('.') {
+                    skipped += 1;
+                }
+            }
+            // a period here belongs to a character that has already scrolled out
+            while characters.next_if_eq(&'.').is_some() {}
+            render(characters, segments);
+        }
+
+        for (display, display_segments) in displays.iter_mut().zip(segments.chunks(DIGITS_PER_DISPLAY)) {
+            for (position, position_segments) in display_segments.iter().enumerate() {
+                display.set_segments(position, *position_segments);
+            }
+        }
+    }
+}
+
+
+/// Fills the segments from the characters, attaching each period to the preceding character.
+///
+/// Because periods do not occupy a digit, they are not counted when scrolling.
+fn render<I: Iterator<Item = char>>(characters: I, segments: &mut [SegmentCombo]) {
+    let mut position = 0;
+    for character in characters {
+        if character == '.' {
+            if position > 0 {
+                segments[position - 1] |= SegmentCombo::DECIMAL_POINT;
+            }
+            continue;
+        }
+        if position == segments.len() {
+            break;
+        }
+        segments[position] = glyph(character).unwrap_or(SegmentCombo::empty());
+        position += 1;
+    }
+}
+
+
+/// A fixed-capacity buffer into which messages for a marquee can be formatted.
+///
+/// Text beyond the capacity is cut off.
+pub struct MessageBuffer<const SIZE: usize> {
+    buffer: [u8; SIZE],
+    length: usize,
+}
+impl<const SIZE: usize> MessageBuffer<SIZE> {
+    pub const fn new() -> Self {
+        Self {
+            buffer: [0; SIZE],
+            length: 0,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        // only whole characters are ever appended
+        core::str::from_utf8(&self.buffer[..self.length]).unwrap()
+    }
+}
+impl<const SIZE: usize> Default for MessageBuffer<SIZE> {
+    fn default() -> Self { Self::new() }
+}
+impl<const SIZE: usize> fmt::Write for MessageBuffer<SIZE> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for character in s.chars() {
+            let character_length = character.len_utf8();
+            if self.length + character_length > SIZE {
+                break;
+            }
+            character.encode_utf8(&mut self.buffer[self.length..]);
+            self.length += character_length;
+        }
+        Ok(())
+    }
+}
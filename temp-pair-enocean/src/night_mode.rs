@@ -0,0 +1,125 @@
+//! Blanking all displays at night.
+//!
+//! Night falls when the ambient light has stayed below a threshold for a while or when the wall
+//! clock enters a scheduled period; it can also be started and ended by hand. During the night, a
+//! keypress wakes the displays for a short time.
+
+
+/// When night falls, taken from the settings.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct NightSchedule {
+    /// The ambient light below which it is dark, in lux; 0 if the light does not matter.
+    pub light_threshold_lux: u16,
+
+    /// How long it has to be dark before night falls, in seconds.
+    pub light_delay_seconds: u16,
+
+    /// The minute of the day at which night falls.
+    pub from_minute: u16,
+
+    /// The minute of the day at which night ends; if equal to `from_minute`, the time of day does
+    /// not matter.
+    pub until_minute: u16,
+
+    /// How long a keypress wakes the displays, in seconds.
+    pub wake_seconds: u16,
+}
+impl NightSchedule {
+    /// Returns whether the given minute of the day lies within the scheduled night.
+    fn is_scheduled(&self, minute_of_day: u16) -> bool {
+        if self.from_minute == self.until_minute {
+            false
+        } else if self.from_minute < self.until_minute {
+            (self.from_minute..self.until_minute).contains(&minute_of_day)
+        } else {
+            // past midnight
+            minute_of_day >= self.from_minute || minute_of_day < self.until_minute
+        }
+    }
+}
+
+
+pub struct NightMode {
+    /// The value of the SysTick counter since which the ambient light has been below the
+    /// threshold.
+    dark_since: Option<u32>,
+
+    /// Whether the light or the schedule call for night.
+    automatic: bool,
+
+    /// Whether night has been started (`Some(true)`) or ended (`Some(false)`) by hand; this lasts
+    /// until the light or the schedule change their mind.
+    manual: Option<bool>,
+
+    /// The value of the SysTick counter at which a keypress woke the displays.
+    woken_at: Option<u32>,
+}
+impl NightMode {
+    pub const fn new() -> Self {
+        Self {
+            dark_since: None,
+            automatic: false,
+            manual: None,
+            woken_at: None,
+        }
+    }
+
+    /// Returns whether it is night, whether or not the displays have been woken.
+    pub fn is_night(&self) -> bool {
+        self.manual.unwrap_or(self.automatic)
+    }
+
+    /// Updates the state from the ambient light (in lux) and the time of day (`None` if unknown);
+    /// `counter` is the current value of the SysTick counter.
+    pub fn update(&mut self, schedule: &NightSchedule, light_lux: u32, minute_of_day: Option<u16>, counter: u32) {
+        if light_lux < u32::from(schedule.light_threshold_lux) {
+            self.dark_since.get_or_insert(counter);
+        } else {
+            self.dark_since = None;
+        }
+        let dark_long_enough = self.dark_since
+            .map(|since| counter.wrapping_sub(since) >= u32::from(schedule.light_delay_seconds) * 1000)
+            .unwrap_or(false);
+        let scheduled = minute_of_day
+            .map(|minute| schedule.is_scheduled(minute))
+            .unwrap_or(false);
+
+        let automatic = dark_long_enough || scheduled;
+        if self.automatic != automatic {
+            self.automatic = automatic;
+            self.manual = None;
+        }
+
+        if let Some(woken_at) = self.woken_at
+            && counter.wrapping_sub(woken_at) >= u32::from(schedule.wake_seconds) * 1000
+        {
+            self.woken_at = None;
+        }
+        if !self.is_night() {
+            self.woken_at = None;
+        }
+    }
+
+    /// Starts night if it is day and ends it if it is night.
+    pub fn toggle(&mut self) {
+        self.manual = Some(!self.is_night());
+        self.woken_at = None;
+    }
+
+    /// Wakes the displays for a while if they are blanked; `counter` is the current value of the
+    /// SysTick counter.
+    ///
+    /// Returns whether the displays were blanked.
+    pub fn wake(&mut self, counter: u32) -> bool {
+        let was_blanked = self.is_blanked();
+        if self.is_night() {
+            self.woken_at = Some(counter);
+        }
+        was_blanked
+    }
+
+    /// Returns whether the displays should currently be blanked.
+    pub fn is_blanked(&self) -> bool {
+        self.is_night() && self.woken_at.is_none()
+    }
+}
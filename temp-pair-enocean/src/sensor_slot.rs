@@ -0,0 +1,136 @@
+//! Configuration of the outside and inside sensor, persisted in external flash.
+//!
+//! The record starts with the sender ID (4 bytes) and profile (3 bytes) of the outside sensor,
+//! followed by the same for the inside sensor. The calibration of both sensors is appended; a
+//! calibration outside the permitted ranges (such as erased flash) is replaced by the default
+//! calibration, so records written by an older firmware remain valid.
+
+
+use crate::eep::Measurements;
+use crate::flash::Address;
+
+
+/// The location of the sensor slots in flash (the first 4 KiB sector).
+pub const SENSOR_SLOTS_ADDRESS: Address = Address::new(0x00_0000).unwrap();
+
+
+/// Corrects the temperature reported by a sensor.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Calibration {
+    /// Added to the temperature after scaling, in units of 0.1 °C.
+    pub offset_tenth_celsius: i16,
+
+    /// The factor by which the temperature is scaled, in units of 0.001.
+    pub gain_permille: u16,
+}
+impl Calibration {
+    pub const SERIALIZED_LENGTH: usize = 4;
+    pub const OFFSET_RANGE_TENTH_CELSIUS: (i16, i16) = (-100, 100);
+    pub const GAIN_RANGE_PERMILLE: (u16, u16) = (500, 1500);
+
+    /// Returns the calibration if the offset and gain are within their ranges.
+    pub fn new(offset_tenth_celsius: i16, gain_permille: u16) -> Option<Self> {
+        let (offset_min, offset_max) = Self::OFFSET_RANGE_TENTH_CELSIUS;
+        let (gain_min, gain_max) = Self::GAIN_RANGE_PERMILLE;
+        if !(offset_min..=offset_max).contains(&offset_tenth_celsius) {
+            return None;
+        }
+        if !(gain_min..=gain_max).contains(&gain_permille) {
+            return None;
+        }
+        Some(Self { offset_tenth_celsius, gain_permille })
+    }
+
+    /// Applies the calibration to a temperature in units of 0.1 °C.
+    pub fn apply(&self, tenth_celsius: i32) -> i32 {
+        let scaled = tenth_celsius.saturating_mul(i32::from(self.gain_permille));
+        let rounded = scaled.saturating_add(500 * scaled.signum()) / 1000;
+        rounded + i32::from(self.offset_tenth_celsius)
+    }
+
+    /// Applies the calibration to the temperature contained in the measurements, if any.
+    pub fn apply_to(&self, mut measurements: Measurements) -> Measurements {
+        measurements.temperature_tenth_celsius = measurements.temperature_tenth_celsius
+            .map(|t| self.apply(t));
+        measurements
+    }
+
+    pub fn from_bytes(bytes: &[u8; Self::SERIALIZED_LENGTH]) -> Self {
+        Self::new(
+            i16::from_be_bytes(bytes[0..2].try_into().unwrap()),
+            u16::from_be_bytes(bytes[2..4].try_into().unwrap()),
+        )
+            .unwrap_or_default()
+    }
+
+    pub fn to_bytes(self) -> [u8; Self::SERIALIZED_LENGTH] {
+        let offset = self.offset_tenth_celsius.to_be_bytes();
+        let gain = self.gain_permille.to_be_bytes();
+        [offset[0], offset[1], gain[0], gain[1]]
+    }
+}
+impl Default for Calibration {
+    fn default() -> Self {
+        Self {
+            offset_tenth_celsius: 0,
+            gain_permille: 1000,
+        }
+    }
+}
+
+
+/// A configured sensor.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct SensorSlot {
+    /// The sender ID.
+    pub address: u32,
+
+    /// The profile in the form `0x00RRFFTT`.
+    pub format: u32,
+
+    pub calibration: Calibration,
+}
+
+
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct SensorSlots {
+    pub outside: SensorSlot,
+    pub inside: SensorSlot,
+}
+impl SensorSlots {
+    /// The number of bytes occupied by the sensor slots in flash.
+    pub const SERIALIZED_LENGTH: usize = 2 * 7 + 2 * Calibration::SERIALIZED_LENGTH;
+
+    pub fn from_bytes(bytes: &[u8; Self::SERIALIZED_LENGTH]) -> Self {
+        let address_and_format = |offset: usize| {
+            let address = u32::from_be_bytes(bytes[offset..offset+4].try_into().unwrap());
+            let format = u32::from_be_bytes([0, bytes[offset+4], bytes[offset+5], bytes[offset+6]]);
+            (address, format)
+        };
+        let (outside_address, outside_format) = address_and_format(0);
+        let (inside_address, inside_format) = address_and_format(7);
+        Self {
+            outside: SensorSlot {
+                address: outside_address,
+                format: outside_format,
+                calibration: Calibration::from_bytes(bytes[14..18].try_into().unwrap()),
+            },
+            inside: SensorSlot {
+                address: inside_address,
+                format: inside_format,
+                calibration: Calibration::from_bytes(bytes[18..22].try_into().unwrap()),
+            },
+        }
+    }
+
+    pub fn to_bytes(self) -> [u8; Self::SERIALIZED_LENGTH] {
+        let mut ret = [0u8; Self::SERIALIZED_LENGTH];
+        for (offset, slot) in [(0, &self.outside), (7, &self.inside)] {
+            ret[offset..offset+4].copy_from_slice(&slot.address.to_be_bytes());
+            ret[offset+4..offset+7].copy_from_slice(&slot.format.to_be_bytes()[1..4]);
+        }
+        ret[14..18].copy_from_slice(&self.outside.calibration.to_bytes());
+        ret[18..22].copy_from_slice(&self.inside.calibration.to_bytes());
+        ret
+    }
+}
@@ -0,0 +1,243 @@
+//! Application settings that are persisted in external flash.
+//!
+//! The settings are stored as a flat sequence of big-endian fields. A field whose bytes are all
+//! 0xFF (the value of erased flash) takes its default value; this way, fields can be appended
+//! without invalidating settings written by an older firmware. Consequently, a value whose bytes
+//! are all 0xFF (such as -1 in a signed field) is out of range for every field.
+
+
+use crate::flash::Address;
+
+
+/// The location of the settings in flash (the second 4 KiB sector).
+pub const SETTINGS_ADDRESS: Address = Address::new(0x00_1000).unwrap();
+
+
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum SettingError {
+    UnknownName,
+    OutOfRange,
+
+    /// The value does not fit together with the value of another setting.
+    Conflict,
+}
+
+
+macro_rules! define_settings {
+    (
+        $(
+            $(#[$attr:meta])*
+            $field:ident : $type:ty = $default:expr, $name:literal, $min:expr, $max:expr;
+        )*
+    ) => {
+        #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+        pub struct Settings {
+            $(
+                $(#[$attr])*
+                pub $field: $type,
+            )*
+        }
+        impl Settings {
+            /// The names of all settings, as used by [`get`](Settings::get) and
+            /// [`set`](Settings::set).
+            pub const NAMES: &'static [&'static str] = &[$($name),*];
+
+            /// The number of bytes occupied by the settings in flash.
+            pub const SERIALIZED_LENGTH: usize = 0 $(+ size_of::<$type>())*;
+
+            pub fn from_bytes(bytes: &[u8; Self::SERIALIZED_LENGTH]) -> Self {
+                let mut defaults = Self::default();
+                let mut offset = 0;
+                $(
+                    let field_bytes = &bytes[offset..offset+size_of::<$type>()];
+                    if field_bytes.iter().any(|b| *b != 0xFF) {
+                        defaults.$field = <$type>::from_be_bytes(field_bytes.try_into().unwrap());
+                    }
+                    offset += size_of::<$type>();
+                )*
+                debug_assert_eq!(offset, Self::SERIALIZED_LENGTH);
+                defaults
+            }
+
+            pub fn to_bytes(self) -> [u8; Self::SERIALIZED_LENGTH] {
+                let mut ret = [0u8; Self::SERIALIZED_LENGTH];
+                let mut offset = 0;
+                $(
+                    ret[offset..offset+size_of::<$type>()].copy_from_slice(&self.$field.to_be_bytes());
+                    offset += size_of::<$type>();
+                )*
+                debug_assert_eq!(offset, Self::SERIALIZED_LENGTH);
+                ret
+            }
+
+            pub fn get(&self, name: &str) -> Option<i64> {
+                match name {
+                    $(
+                        $name => Some(self.$field.into()),
+                    )*
+                    _ => None,
+                }
+            }
+
+            pub fn set(&mut self, name: &str, value: i64) -> Result<(), SettingError> {
+                match name {
+                    $(
+                        $name => {
+                            if !($min..=$max).contains(&value) {
+                                return Err(SettingError::OutOfRange);
+                            }
+                            let field_value: $type = value.try_into()
+                                .map_err(|_| SettingError::OutOfRange)?;
+                            if field_value.to_be_bytes().iter().all(|b| *b == 0xFF) {
+                                // would be read back as the default
+                                return Err(SettingError::OutOfRange);
+                            }
+                            let mut candidate = *self;
+                            candidate.$field = field_value;
+                            if !candidate.is_consistent() {
+                                return Err(SettingError::Conflict);
+                            }
+                            *self = candidate;
+                            Ok(())
+                        },
+                    )*
+                    _ => Err(SettingError::UnknownName),
+                }
+            }
+        }
+        impl Default for Settings {
+            fn default() -> Self {
+                Self {
+                    $(
+                        $field: $default,
+                    )*
+                }
+            }
+        }
+    };
+}
+
+impl Settings {
+    /// Returns whether the settings fit together.
+    fn is_consistent(&self) -> bool {
+        // otherwise, the CO2 alert would be raised and withdrawn at the same concentration
+        self.co2_clear_ppm < self.co2_alert_ppm
+            // otherwise, the curves would be silently clamped
+            && self.brightness_min <= self.brightness_max
+            && self.setpoint_min_tenth_celsius <= self.setpoint_max_tenth_celsius
+    }
+}
+
+define_settings! {
+    /// The inside temperature we are aiming for, in units of 0.1 °C.
+    comfort_tenth_celsius: i16 = 210, "comfort", -400, 600;
+
+    /// How far the inside temperature may stray from the comfort temperature before we recommend
+    /// opening the window, in units of 0.1 °C.
+    comfort_hysteresis_tenth_celsius: u16 = 10, "hysteresis", 0, 200;
+
+    /// After how many seconds without a new value a sensor reading is considered stale.
+    stale_after_seconds: u16 = 1800, "stale", 1, 0xFFFE;
+
+    /// The CO2 concentration, in ppm, at or above which we urge to ventilate.
+    co2_alert_ppm: u16 = 1400, "co2_alert", 0, 10000;
+
+    /// The CO2 concentration, in ppm, at or below which the ventilation alert is withdrawn; must be
+    /// below `co2_alert_ppm`.
+    co2_clear_ppm: u16 = 1000, "co2_clear", 0, 10000;
+
+    /// The sender ID of the window handle (F6-10-00) or window contact (D5-00-01), or 0 if there
+    /// is none.
+    window_sensor_address: u32 = 0, "window_sensor", 0, 0xFFFF_FFFE;
+
+    /// The sender ID of the rocker switch (F6-02-01/02) used as a remote control, or 0 if there is
+    /// none.
+    rocker_switch_address: u32 = 0, "rocker_switch", 0, 0xFFFF_FFFE;
+
+    /// Whether valve actuators (A5-20-01) are answered with the comfort temperature as their
+    /// setpoint (1) or not (0).
+    valve_reply: u8 = 0, "valve_reply", 0, 1;
+
+    /// Whether the setpoint dial of a room operating panel (A5-10-xx) replaces the comfort
+    /// temperature (1) or not (0).
+    use_setpoint: u8 = 0, "use_setpoint", 0, 1;
+
+    /// The temperature corresponding to the minimum position of the setpoint dial, in units of
+    /// 0.1 °C; must not be above `setpoint_max_tenth_celsius`.
+    setpoint_min_tenth_celsius: i16 = 150, "setpoint_min", -400, 600;
+
+    /// The temperature corresponding to the maximum position of the setpoint dial, in units of
+    /// 0.1 °C.
+    setpoint_max_tenth_celsius: i16 = 300, "setpoint_max", -400, 600;
+
+    /// The sender ID of the occupancy sensor, or 0 if there is none.
+    occupancy_sensor_address: u32 = 0, "occupancy_sensor", 0, 0xFFFF_FFFE;
+
+    /// The profile of the occupancy sensor (A5-07-01 to A5-07-03 or A5-08-01 to A5-08-03).
+    occupancy_sensor_profile: u32 = 0xA5_07_01, "occupancy_profile", 0, 0xFF_FF_FF;
+
+    /// After how many seconds without anybody present the displays are turned off, or 0 to keep
+    /// them on.
+    absence_blank_seconds: u16 = 0, "absence_blank", 0, 0xFFFE;
+
+    /// Whether temperatures are displayed in degrees Fahrenheit (1) or Celsius (0).
+    fahrenheit: u8 = 0, "fahrenheit", 0, 1;
+
+    /// How long scrolling text stays in place before moving on by one digit, in milliseconds.
+    marquee_step_millis: u16 = 400, "marquee_step", 50, 5000;
+
+    /// The intensity of the decimal points relative to the other segments, in percent.
+    decimal_point_intensity_percent: u8 = 100, "dp_intensity", 0, 100;
+
+    /// How long a digit takes to cross-fade to a new value, in milliseconds, or 0 to switch
+    /// immediately.
+    fade_millis: u16 = 0, "fade", 0, 2000;
+
+    /// Over how many minutes the temperature trends are computed.
+    trend_window_minutes: u16 = 30, "trend_window", 2, 60;
+
+    /// By how much a temperature must change over the trend window to be considered rising or
+    /// falling, in units of 0.1 °C.
+    trend_threshold_tenth_celsius: u16 = 5, "trend_threshold", 1, 200;
+
+    /// The ambient light (in lux) below which night falls after `night_light_delay_seconds`, or 0
+    /// if the ambient light does not start the night.
+    night_light_threshold_lux: u16 = 0, "night_light", 0, 0xFFFE;
+
+    /// How long the ambient light must stay below `night_light_threshold_lux` before night falls, in
+    /// seconds.
+    night_light_delay_seconds: u16 = 300, "night_delay", 0, 0xFFFE;
+
+    /// The minute of the day (0 is midnight) at which night falls.
+    night_from_minute: u16 = 0, "night_from", 0, 1439;
+
+    /// The minute of the day at which night ends; if equal to `night_from_minute`, the time of day
+    /// does not start the night.
+    night_until_minute: u16 = 0, "night_until", 0, 1439;
+
+    /// How long a keypress wakes the displays at night, in seconds.
+    night_wake_seconds: u16 = 30, "night_wake", 1, 0xFFFE;
+
+    /// The brightness of the 7-segment displays in complete darkness (of 4095); must not be above
+    /// `brightness_max`.
+    brightness_min: u16 = 1, "brightness_min", 1, 4095;
+
+    /// The brightness of the 7-segment displays at `brightness_full_lux` and above (of 4095).
+    brightness_max: u16 = 4095, "brightness_max", 1, 4095;
+
+    /// The ambient light (in lux) at and above which the 7-segment displays are at
+    /// `brightness_max`.
+    brightness_full_lux: u16 = 1000, "brightness_lux", 1, 0xFFFE;
+
+    /// The gamma of the curve mapping the ambient light onto the brightness, in units of 0.1; the
+    /// brightness follows the ambient light raised to the power of 1/gamma.
+    brightness_gamma_tenths: u8 = 20, "brightness_gamma", 5, 40;
+
+    /// The time constant of the low-pass filter smoothing the brightness, in milliseconds, or 0 to
+    /// follow the ambient light immediately.
+    brightness_smoothing_millis: u16 = 2000, "brightness_smoothing", 0, 60000;
+
+    /// How far (in percent) the smoothed brightness must move away from the current brightness
+    /// before the displays follow it.
+    brightness_hysteresis_percent: u8 = 5, "brightness_hysteresis", 0, 50;
+}
@@ -41,6 +41,13 @@ pub fn get_counter() -> u32 {
     })
 }
 
+/// Returns the number of milliseconds that have passed since the given counter value.
+///
+/// Correctly handles a single wraparound of the counter.
+pub fn millis_since(earlier_counter: u32) -> u32 {
+    get_counter().wrapping_sub(earlier_counter)
+}
+
 #[exception]
 fn SysTick() {
     critical_section::with(|cs| {
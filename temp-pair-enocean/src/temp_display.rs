@@ -1,285 +1,260 @@
-/// Temperature display logic.
-use bitflags::bitflags;
+/// Temperature display transports.
+#[cfg(not(feature = "direct-spi-display"))]
+use core::cell::Cell;
+#[cfg(not(feature = "direct-spi-display"))]
+use core::fmt::Write;
+use core::marker::PhantomData;
+
 use stm32f7::stm32f745::Peripherals;
+use tpe_seven_segment::{SegmentCombo, SevenSegmentDisplay, TempDisplayState};
 
+#[cfg(not(feature = "direct-spi-display"))]
+use crate::console::ConsoleWriter;
+#[cfg(feature = "direct-spi-display")]
+use crate::gpio_output::GpioOutput;
+#[cfg(not(feature = "direct-spi-display"))]
 use crate::i2c::{I2c, I2cAddress};
+#[cfg(feature = "direct-spi-display")]
 use crate::spi::{Spi, Spi1};
 
 
-/// A 12-bit brightness value.
-#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
-pub struct Brightness {
-    inner: u16,
-}
-impl Brightness {
-    pub const fn new(inner: u16) -> Option<Self> {
-        if inner <= 0x0FFF {
-            Some(Self { inner })
-        } else {
-            None
+/// How often the interrupt line is checked before giving up on the bridge signaling the end of a
+/// transfer; shifting out 36 bytes at 1875 kHz takes about 150 µs, much less than this.
+#[cfg(not(feature = "direct-spi-display"))]
+const TRANSFER_COMPLETE_POLLS: u32 = 16_384;
+
+/// The order in which the segments of a digit are connected to the outputs of the TLC5947.
+const ELEMENTS: [SegmentCombo; 8] = [
+    SegmentCombo::DECIMAL_POINT,
+    SegmentCombo::MIDDLE,
+    SegmentCombo::TOP_LEFT,
+    SegmentCombo::BOTTOM_LEFT,
+    SegmentCombo::BOTTOM,
+    SegmentCombo::BOTTOM_RIGHT,
+    SegmentCombo::TOP_RIGHT,
+    SegmentCombo::TOP,
+];
+
+/// Writes out the 12-bit PWM values of all segments in the format expected by a TLC5947, which
+/// takes 36 bytes for the 24 outputs.
+fn write_tlc5947_bytes(content: &TempDisplayState, spi_bytes: &mut [u8]) {
+    assert_eq!(spi_bytes.len(), 36);
+
+    for i in 0..content.lit_segments().len() {
+        let digit_ret_offset = 12 * i;
+
+        // technically u24
+        // from:
+        // 0000 0000 aaaa aaaa aaaa bbbb bbbb bbbb
+        // 0000 0000 cccc cccc cccc dddd dddd dddd
+        // 0000 0000 eeee eeee eeee ffff ffff ffff
+        // 0000 0000 gggg gggg gggg hhhh hhhh hhhh
+        // to:
+        // aaaa aaaa | aaaa bbbb | bbbb bbbb
+        // cccc cccc | cccc dddd | dddd dddd
+        // eeee eeee | eeee ffff | ffff ffff
+        // gggg gggg | gggg hhhh | hhhh hhhh
+        // (12 bytes per 7-seg display => 36 per controller)
+        let mut brightness_pairs = [0u32; 4];
+        for (j, brightness_pair) in brightness_pairs.iter_mut().enumerate() {
+            *brightness_pair |= u32::from(content.segment_level(i, ELEMENTS[2*j])) << 12;
+            *brightness_pair |= u32::from(content.segment_level(i, ELEMENTS[2*j + 1]));
         }
-    }
 
-    pub const fn as_u16(&self) -> u16 { self.inner }
-}
-impl TryFrom<u16> for Brightness {
-    type Error = ();
-    fn try_from(value: u16) -> Result<Self, Self::Error> {
-        Self::new(value).ok_or(())
+        for (j, brightness_pair) in brightness_pairs.iter().copied().enumerate() {
+            let pair_ret_offset = digit_ret_offset + 3 * j;
+            spi_bytes[pair_ret_offset..pair_ret_offset+3]
+                .copy_from_slice(&brightness_pair.to_be_bytes()[1..4]);
+        }
     }
 }
-impl From<Brightness> for u16 {
-    fn from(value: Brightness) -> Self { value.as_u16() }
-}
 
 
-bitflags! {
-    #[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
-    pub struct SegmentCombo : u8 {
-        const DECIMAL_POINT = 0b0000_0001;
-        const MIDDLE = 0b0000_0010;
-        const TOP_LEFT = 0b0000_0100;
-        const BOTTOM_LEFT = 0b0000_1000;
-        const BOTTOM = 0b0001_0000;
-        const BOTTOM_RIGHT = 0b0010_0000;
-        const TOP_RIGHT = 0b0100_0000;
-        const TOP = 0b1000_0000;
-    }
+/// Which pin latches the data shifted into a TLC5947 behind the I2C-SPI bridge.
+#[cfg(not(feature = "direct-spi-display"))]
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Latch {
+    /// The CS0 GPIO of the I2C-SPI bridge.
+    Chip1,
+
+    /// IO1 of the I2C port expander.
+    Chip2,
 }
 
 
-const SUPPORTED_CHARACTERS_SORTED: [u8; 24] = [
-    b' ', b'-', b'0', b'1',
-    b'2', b'3', b'4', b'5',
-    b'6', b'7', b'8', b'9',
-    b'A', b'B', b'C', b'D',
-    b'E', b'F', b'a', b'b',
-    b'c', b'd', b'e', b'f',
-];
-// same order as SUPPORTED_CHARACTERS_SORTED
-const CHARACTER_SEGMENTS: [SegmentCombo; 24] = {
-    const M: u8 = SegmentCombo::MIDDLE.bits();
-    const T: u8 = SegmentCombo::TOP.bits();
-    const TL: u8 = SegmentCombo::TOP_LEFT.bits();
-    const BL: u8 = SegmentCombo::BOTTOM_LEFT.bits();
-    const B: u8 = SegmentCombo::BOTTOM.bits();
-    const BR: u8 = SegmentCombo::BOTTOM_RIGHT.bits();
-    const TR: u8 = SegmentCombo::TOP_RIGHT.bits();
-
-    [
-        SegmentCombo::from_bits_retain(0), // space
-        SegmentCombo::from_bits_retain(M), // -
-        SegmentCombo::from_bits_retain(T | TL | BL | B | BR | TR), // 0
-        SegmentCombo::from_bits_retain(TR | BR), // 1
-        SegmentCombo::from_bits_retain(T | TR | M | BL | B), // 2
-        SegmentCombo::from_bits_retain(T | TR | M | BR | B), // 3
-        SegmentCombo::from_bits_retain(TL | M | TR | BR), // 4
-        SegmentCombo::from_bits_retain(T | TL | M | BR | B), // 5
-        SegmentCombo::from_bits_retain(T | TL | BL | B | BR | M), // 6
-        SegmentCombo::from_bits_retain(TL | T | TR | BR), // 7
-        SegmentCombo::from_bits_retain(T | TL | TR | M | BL | BR | B), // 8
-        SegmentCombo::from_bits_retain(T | TL | TR | M | BR | B), // 9
-        SegmentCombo::from_bits_retain(BL | TL | T | TR | BR | M), // A
-        SegmentCombo::from_bits_retain(TL | BL | B | BR | M), // b
-        SegmentCombo::from_bits_retain(T | TL | BL | B), // C
-        SegmentCombo::from_bits_retain(TR | BL | B | BR | M), // d
-        SegmentCombo::from_bits_retain(T | TL | M | BL | B), // E
-        SegmentCombo::from_bits_retain(T | TL | M | BL), // F
-        SegmentCombo::from_bits_retain(T | TR | M | BL | BR | B), // a
-        SegmentCombo::from_bits_retain(TL | BL | B | BR | M), // b
-        SegmentCombo::from_bits_retain(M | BL | B), // c
-        SegmentCombo::from_bits_retain(TR | BL | B | BR | M), // d
-        SegmentCombo::from_bits_retain(T | TL | TR | M | BL | B), // e
-        SegmentCombo::from_bits_retain(T | TL | M | BL), // F
-    ]
-};
-
-
-pub struct TempDisplayState {
-    lit_segments: [SegmentCombo; 3],
-    brightness: Brightness,
-    reversed_order: bool,
-    dirty: bool,
+/// A TLC5947-driven display behind the I2C-SPI bridge.
+///
+/// Both TLC5947 chips are connected in parallel to the SPI bus of the bridge, so one longer transfer
+/// with the data for both displays would leave the same last 36 bytes in both shift registers. Each
+/// display therefore needs a transfer of its own, latched through its own pin (CS0 of the bridge
+/// for chip 1, IO1 of the port expander for chip 2).
+#[cfg(not(feature = "direct-spi-display"))]
+pub struct BridgedTlc5947Display<'d, I: I2c> {
+    displays: &'d I2cSpiBridgedTempDisplays,
+    latch: Latch,
+
+    /// Returns whether the interrupt line of the bridge is pulled low.
+    ///
+    /// The INT output of the SC18IS606 goes to the mikroBUS INT pin of the I2C SPI Shuttle board
+    /// (see the schematic in the mikrobus-boards repository linked from the README), and the
+    /// shuttle shares slot 3, and thereby PD10, with the Ambient 24 Click (see the mikroBUS pin
+    /// table in the README).
+    interrupt_asserted: fn(&Peripherals) -> bool,
+
+    /// Whether the bridge did not signal the end of the last transfer in time, which means the
+    /// display has not been latched and the transfer has to be repeated.
+    transfer_timed_out: bool,
+
+    i2c: PhantomData<I>,
 }
-impl TempDisplayState {
-    pub fn new(reversed_order: bool) -> Self {
+#[cfg(not(feature = "direct-spi-display"))]
+impl<'d, I: I2c> BridgedTlc5947Display<'d, I> {
+    pub const fn new(
+        displays: &'d I2cSpiBridgedTempDisplays,
+        latch: Latch,
+        interrupt_asserted: fn(&Peripherals) -> bool,
+    ) -> Self {
         Self {
-            lit_segments: [SegmentCombo::empty(); 3],
-            brightness: Brightness::new(1).unwrap(),
-            reversed_order,
-            dirty: false,
+            displays,
+            latch,
+            interrupt_asserted,
+            transfer_timed_out: false,
+            i2c: PhantomData,
         }
     }
 
-    /// Writes out which segments are lit.
-    ///
-    /// Does not modify the dirty flag.
-    #[allow(unused)]
-    pub fn write_lit_segments(&self, segments: &mut [u8]) {
-        assert_eq!(segments.len(), 3);
-
-        const ELEMENTS: [SegmentCombo; 8] = [
-            SegmentCombo::DECIMAL_POINT,
-            SegmentCombo::MIDDLE,
-            SegmentCombo::TOP_LEFT,
-            SegmentCombo::BOTTOM_LEFT,
-            SegmentCombo::BOTTOM,
-            SegmentCombo::BOTTOM_RIGHT,
-            SegmentCombo::TOP_RIGHT,
-            SegmentCombo::TOP,
-        ];
-
-        for (i, digit_lit_segments) in self.lit_segments.iter().copied().enumerate() {
-            segments[i] = 0;
-            for (j, segment) in ELEMENTS.into_iter().enumerate() {
-                if digit_lit_segments.contains(segment) {
-                    segments[i] |= 1 << j;
-                }
-            }
+    fn set_latch(&self, peripherals: &Peripherals, up: bool) {
+        match self.latch {
+            Latch::Chip1 => self.displays.set_chip_1_xlat::<I>(peripherals, up),
+            Latch::Chip2 => self.displays.set_chip_2_xlat::<I>(peripherals, up),
         }
     }
+}
+#[cfg(not(feature = "direct-spi-display"))]
+impl<I: I2c> SevenSegmentDisplay for BridgedTlc5947Display<'_, I> {
+    type Context = Peripherals;
 
-    fn write_spi_bytes(&self, spi_bytes: &mut [u8]) {
-        assert_eq!(spi_bytes.len(), 36);
-
-        const ELEMENTS: [SegmentCombo; 8] = [
-            SegmentCombo::DECIMAL_POINT,
-            SegmentCombo::MIDDLE,
-            SegmentCombo::TOP_LEFT,
-            SegmentCombo::BOTTOM_LEFT,
-            SegmentCombo::BOTTOM,
-            SegmentCombo::BOTTOM_RIGHT,
-            SegmentCombo::TOP_RIGHT,
-            SegmentCombo::TOP,
-        ];
-
-        for (i, lit_segment) in self.lit_segments.iter().copied().enumerate() {
-            let digit_ret_offset = 12 * i;
-
-            // technically u24
-            // from:
-            // 0000 0000 aaaa aaaa aaaa bbbb bbbb bbbb
-            // 0000 0000 cccc cccc cccc dddd dddd dddd
-            // 0000 0000 eeee eeee eeee ffff ffff ffff
-            // 0000 0000 gggg gggg gggg hhhh hhhh hhhh
-            // to:
-            // aaaa aaaa | aaaa bbbb | bbbb bbbb
-            // cccc cccc | cccc dddd | dddd dddd
-            // eeee eeee | eeee ffff | ffff ffff
-            // gggg gggg | gggg hhhh | hhhh hhhh
-            // (12 bytes per 7-seg display => 36 per controller)
-            let mut brightness_pairs = [0u32; 4];
-            for (element_pair, brightness_pair) in ELEMENTS.chunks(2).zip(brightness_pairs.iter_mut()) {
-                let first_element = element_pair[0];
-                let second_element = element_pair[1];
-
-                if lit_segment.contains(first_element) {
-                    *brightness_pair |= u32::from(self.brightness.as_u16()) << 12;
-                }
-                if lit_segment.contains(second_element) {
-                    *brightness_pair |= u32::from(self.brightness.as_u16()) << 0;
+    fn transmit(&mut self, peripherals: &Peripherals, content: &TempDisplayState) {
+        // the interrupt line may be shared; if someone else is already holding it low,
+        // we cannot tell when the bridge is done
+        let interrupt_usable = !(self.interrupt_asserted)(peripherals);
+
+        let mut i2c_bytes = [0u8; 37];
+        i2c_bytes[0] = self.displays.chip_select_pattern;
+        write_tlc5947_bytes(content, &mut i2c_bytes[1..37]);
+        self.displays.send_spi_data::<I>(peripherals, &i2c_bytes);
+
+        // the data is only transmitted on the SPI bus
+        // when the the transmission on the I2C bus has finished;
+        // the bridge pulls its interrupt line low once it is done
+        let previously_timed_out = self.transfer_timed_out;
+        self.transfer_timed_out = false;
+        if interrupt_usable {
+            let mut polls_left = TRANSFER_COMPLETE_POLLS;
+            while !(self.interrupt_asserted)(peripherals) {
+                if polls_left == 0 {
+                    self.transfer_timed_out = true;
+                    break;
                 }
+                polls_left -= 1;
             }
-
-            for (j, brightness_pair) in brightness_pairs.iter().copied().enumerate() {
-                let segment_ret_offset = 3 * j;
-                spi_bytes[digit_ret_offset + segment_ret_offset + 0] =
-                    ((brightness_pair >> 16) & 0xFF) as u8;
-                spi_bytes[digit_ret_offset + segment_ret_offset + 1] =
-                    ((brightness_pair >> 8) & 0xFF) as u8;
-                spi_bytes[digit_ret_offset + segment_ret_offset + 2] =
-                    ((brightness_pair >> 0) & 0xFF) as u8;
+        } else {
+            for _ in 0..1024 {
+                cortex_m::asm::nop();
             }
         }
-    }
-
-    pub fn is_dirty(&self) -> bool { self.dirty }
-
-    pub fn set_brightness(&mut self, brightness: Brightness) {
-        if self.brightness != brightness {
-            self.brightness = brightness;
-            self.dirty = true;
+        self.displays.clear_interrupt::<I>(peripherals);
+
+        if self.transfer_timed_out {
+            // the shift register may hold half-shifted data; keep showing the previous contents
+            // (and only complain once while the bridge stays silent)
+            if !previously_timed_out {
+                let _ = write!(
+                    ConsoleWriter::new(peripherals),
+                    "7-seg {:?}: SPI transfer not completed, not latching\r\n",
+                    self.latch,
+                );
+            }
+            return;
         }
+
+        // pull the XLAT pin up, then pull it down again
+        // (each I2C write takes far longer than the minimum XLAT pulse width)
+        self.set_latch(peripherals, true);
+        self.set_latch(peripherals, false);
     }
 
-    pub fn set_segments(&mut self, position: usize, segments: SegmentCombo) {
-        assert!(position < 3);
-        let real_position = if self.reversed_order { 2 - position } else { position };
-        if self.lit_segments[real_position] != segments {
-            self.lit_segments[real_position] = segments;
-            self.dirty = true;
+    fn update(&mut self, peripherals: &Peripherals, content: &mut TempDisplayState, force: bool) {
+        // also repeat a transfer that timed out
+        if force || content.is_dirty() || self.transfer_timed_out {
+            self.transmit(peripherals, content);
+            content.mark_clean();
         }
     }
+}
 
-    pub fn set_digit(&mut self, position: usize, ascii_digit: u8, decimal_point: bool) {
-        assert!(position < 3);
-        let digit_pos = match SUPPORTED_CHARACTERS_SORTED.binary_search(&ascii_digit) {
-            Ok(dp) => dp,
-            Err(_) => 0,
-        };
-        let decimal_point_segment = if decimal_point {
-            SegmentCombo::DECIMAL_POINT
-        } else {
-            SegmentCombo::empty()
-        };
-        self.set_segments(position, CHARACTER_SEGMENTS[digit_pos] | decimal_point_segment);
-    }
 
-    pub fn set_nibble_digit(&mut self, position: usize, nibble: u8, decimal_point: bool) {
-        assert!(position < 3);
-        let ascii_digit = if nibble >= 0x10 {
-            b' '
-        } else {
-            [
-                b'0', b'1', b'2', b'3', b'4', b'5', b'6', b'7',
-                b'8', b'9', b'A', b'b', b'C', b'd', b'E', b'F',
-            ][usize::from(nibble)]
-        };
-        self.set_digit(position, ascii_digit, decimal_point);
+/// A TLC5947-driven display on SPI1 of the microcontroller whose XLAT is connected to the pin
+/// `L`.
+#[cfg(feature = "direct-spi-display")]
+pub struct SpiTlc5947Display<L: GpioOutput> {
+    latch: PhantomData<L>,
+}
+#[cfg(feature = "direct-spi-display")]
+impl<L: GpioOutput> SpiTlc5947Display<L> {
+    pub const fn new() -> Self {
+        Self {
+            latch: PhantomData,
+        }
     }
+}
+#[cfg(feature = "direct-spi-display")]
+impl<L: GpioOutput> SevenSegmentDisplay for SpiTlc5947Display<L> {
+    type Context = Peripherals;
 
-    #[allow(unused)]
-    pub fn send_via_spi(&mut self, peripherals: &Peripherals) {
+    fn transmit(&mut self, peripherals: &Peripherals, content: &TempDisplayState) {
         let mut spi_bytes = [0u8; 36];
-        self.write_spi_bytes(&mut spi_bytes);
-        Spi1::communicate_bytes(&peripherals, &mut spi_bytes);
-
-        // the display contents now match our stored data
-        self.dirty = false;
-    }
-
-    pub fn send_via_i2c_spi_bridge<I: I2c>(
-        &mut self,
-        peripherals: &Peripherals,
-        displays: &I2cSpiBridgedTempDisplays,
-        wait: bool,
-    ) {
-        let mut i2c_bytes = [0u8; 37];
-        i2c_bytes[0] = displays.chip_select_pattern;
-        self.write_spi_bytes(&mut i2c_bytes[1..37]);
-        displays.send_spi_data::<I>(peripherals, &i2c_bytes);
+        write_tlc5947_bytes(content, &mut spi_bytes);
 
-        // the display contents now match our stored data
-        self.dirty = false;
+        // communicate_bytes only returns once the last byte has been shifted out
+        Spi1::communicate_bytes(peripherals, &mut spi_bytes);
 
-        // the data is only transmitted on the SPI bus
-        // when the the transmission on the I2C bus has finished
-        if wait {
-            for _ in 0..1024 {
-                cortex_m::asm::nop();
-            }
-        }
+        // pull the XLAT pin up, then pull it down again
+        // (each GPIO write takes longer than the minimum XLAT pulse width)
+        L::set_high(peripherals);
+        L::set_low(peripherals);
     }
 }
 
 
-#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[cfg(not(feature = "direct-spi-display"))]
+#[derive(Debug)]
 pub struct I2cSpiBridgedTempDisplays {
     pub i2c_spi_bridge_address: I2cAddress,
     pub i2c_extender_address: I2cAddress,
     pub chip_select_pattern: u8,
+
+    /// Whether the displays are currently blanked.
+    ///
+    /// The blank pin is shared by both displays and sits in the same port expander register as
+    /// the latch of chip 2, so every write to that register has to repeat it.
+    blank: Cell<bool>,
 }
+#[cfg(not(feature = "direct-spi-display"))]
 impl I2cSpiBridgedTempDisplays {
+    pub const fn new(
+        i2c_spi_bridge_address: I2cAddress,
+        i2c_extender_address: I2cAddress,
+        chip_select_pattern: u8,
+    ) -> Self {
+        Self {
+            i2c_spi_bridge_address,
+            i2c_extender_address,
+            chip_select_pattern,
+            blank: Cell::new(false),
+        }
+    }
+
     pub fn set_up<I: I2c>(&self, peripherals: &Peripherals) {
         // configure the I2C-SPI bridge
         I::write_data(
@@ -380,6 +355,20 @@ impl I2cSpiBridgedTempDisplays {
                 0, // push-pull
             ],
         );
+
+        // release the interrupt line in case a transfer has completed before a reset of the µC
+        self.clear_interrupt::<I>(peripherals);
+    }
+
+    /// Releases the interrupt line, which the bridge pulls low after each transfer.
+    pub fn clear_interrupt<I: I2c>(&self, peripherals: &Peripherals) {
+        I::write_data(
+            peripherals,
+            self.i2c_spi_bridge_address,
+            &[
+                0xF1, // clear interrupt
+            ],
+        );
     }
 
     pub fn send_spi_data<I: I2c>(&self, peripherals: &Peripherals, chip_select_pattern_and_data: &[u8]) {
@@ -414,6 +403,7 @@ impl I2cSpiBridgedTempDisplays {
 
     pub fn set_chip_2_xlat<I: I2c>(&self, peripherals: &Peripherals, up: bool) {
         let pin: u8 = if up { 1 } else { 0 };
+        let blank_pin: u8 = if self.blank.get() { 1 } else { 0 };
         I::write_data(
             peripherals,
             self.i2c_extender_address, // the mikroBUS AN pin is on the GPIO extender
@@ -422,11 +412,32 @@ impl I2cSpiBridgedTempDisplays {
                 (
                     (0b0000 << 4) // IO4-IO7 unused and configured as inputs
                     | (0b0 << 3) // IO3 is an input
-                    | (0b0 << 2) // IO2 is "blank" and should be off
+                    | (blank_pin << 2) // IO2 is "blank"; keep it as it is
                     | (pin << 1) // IO1 is "latch" for chip 2, this is the important one
                     | (0b1 << 0) // IO0 is ~{ClickID} so keep it high
                 )
             ]
         );
     }
+
+    /// Turns off all segments of both displays (or turns them back on) without changing their
+    /// contents.
+    pub fn set_blank<I: I2c>(&self, peripherals: &Peripherals, blank: bool) {
+        self.blank.set(blank);
+        let blank_pin: u8 = if blank { 1 } else { 0 };
+        I::write_data(
+            peripherals,
+            self.i2c_extender_address, // the mikroBUS PWM pin is on the GPIO extender
+            &[
+                0x01, // GPIO output
+                (
+                    (0b0000 << 4) // IO4-IO7 unused and configured as inputs
+                    | (0b0 << 3) // IO3 is an input
+                    | (blank_pin << 2) // IO2 is "blank" to both chips, this is the important one
+                    | (0b0 << 1) // IO1 is "latch" for chip 2 and should be off
+                    | (0b1 << 0) // IO0 is ~{ClickID} so keep it high
+                )
+            ]
+        );
+    }
 }
@@ -0,0 +1,107 @@
+//! Temperature trends derived from recent history.
+
+
+use tpe_ring_buffer::RingBuffer;
+
+
+/// How often a sample is added to the history, in milliseconds.
+const SAMPLE_INTERVAL_MILLIS: u32 = 60_000;
+
+/// The size of the ring buffer; as it keeps one slot free, this is enough for an hour of samples.
+const HISTORY_SIZE: usize = 62;
+
+
+/// The direction in which a temperature is moving.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Trend {
+    Falling,
+    Steady,
+    Rising,
+}
+
+
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+struct Sample {
+    tenth_celsius: i32,
+
+    /// The value of the SysTick counter when the sample was taken.
+    taken_at: u32,
+}
+
+
+/// The recent temperatures of a sensor, sampled once a minute for up to an hour.
+pub struct TemperatureHistory {
+    samples: RingBuffer<Sample, HISTORY_SIZE>,
+}
+impl TemperatureHistory {
+    pub const fn new() -> Self {
+        Self {
+            samples: RingBuffer::new(),
+        }
+    }
+
+    /// Records the current temperature if the previous sample is old enough; `counter` is the
+    /// current value of the SysTick counter.
+    ///
+    /// An unknown temperature clears the history, as there would be a gap in it.
+    pub fn record(&mut self, tenth_celsius: Option<i32>, counter: u32) {
+        let Some(tenth_celsius) = tenth_celsius else {
+            while self.samples.read().is_some() {}
+            return;
+        };
+
+        if let Some(newest) = self.samples.iter().last()
+            && counter.wrapping_sub(newest.taken_at) < SAMPLE_INTERVAL_MILLIS
+        {
+            return;
+        }
+
+        if self.samples.is_full() {
+            self.samples.read();
+        }
+        self.samples.write(Sample { tenth_celsius, taken_at: counter });
+    }
+
+    /// Returns the trend over the last `window_minutes` minutes, or `None` if the history does not
+    /// cover at least half of that window yet.
+    ///
+    /// The temperature is considered steady unless the change over the window, estimated by a
+    /// linear regression, is at least `threshold_tenth_celsius`.
+    pub fn trend(&self, window_minutes: u16, threshold_tenth_celsius: u16, counter: u32) -> Option<Trend> {
+        let window_millis = u32::from(window_minutes) * 60_000;
+        let in_window = || self.samples.iter()
+            .filter(move |s| counter.wrapping_sub(s.taken_at) <= window_millis);
+
+        let oldest = in_window().next()?;
+        let span_millis = counter.wrapping_sub(oldest.taken_at);
+        if span_millis < window_millis / 2 {
+            return None;
+        }
+
+        // least squares with x being the age in seconds and y the temperature
+        let (mut n, mut sum_x, mut sum_y, mut sum_xy, mut sum_xx) = (0i64, 0i64, 0i64, 0i64, 0i64);
+        for sample in in_window() {
+            let x = i64::from(sample.taken_at.wrapping_sub(oldest.taken_at) / 1000);
+            let y = i64::from(sample.tenth_celsius);
+            n += 1;
+            sum_x += x;
+            sum_y += y;
+            sum_xy += x * y;
+            sum_xx += x * x;
+        }
+        let denominator = n * sum_xx - sum_x * sum_x;
+        if n < 2 || denominator == 0 {
+            return None;
+        }
+        let change_over_window = (n * sum_xy - sum_x * sum_y) * i64::from(window_millis / 1000) / denominator;
+
+        let threshold = i64::from(threshold_tenth_celsius);
+        if change_over_window >= threshold {
+            Some(Trend::Rising)
+        } else if change_over_window <= -threshold {
+            Some(Trend::Falling)
+        } else {
+            Some(Trend::Steady)
+        }
+    }
+}
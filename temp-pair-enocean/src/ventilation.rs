@@ -0,0 +1,59 @@
+//! Recommendation whether to open the window, based on the outside and inside temperature.
+
+
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Advice {
+    /// At least one of the temperatures is unknown or stale.
+    #[default] Unknown,
+
+    /// Outside air would bring the room closer to the comfort temperature.
+    OpenWindow,
+
+    /// Outside air would not help; keep the window closed.
+    KeepClosed,
+}
+
+
+/// Decides whether opening the window brings the inside temperature closer to the comfort
+/// temperature.
+///
+/// Opening the window is recommended once the inside temperature strays from the comfort
+/// temperature by more than the hysteresis and the outside temperature lies at least the
+/// hysteresis beyond the inside temperature in the direction of the comfort temperature. Once the
+/// window is recommended open, the recommendation only flips back when the inside temperature
+/// reaches the comfort temperature or the outside air stops being helpful at all.
+///
+/// All temperatures are in units of 0.1 °C.
+pub fn advise(
+    previous: Advice,
+    outside_tenth_celsius: Option<i32>,
+    inside_tenth_celsius: Option<i32>,
+    comfort_tenth_celsius: i32,
+    hysteresis_tenth_celsius: i32,
+) -> Advice {
+    let (outside, inside) = match (outside_tenth_celsius, inside_tenth_celsius) {
+        (Some(o), Some(i)) => (o, i),
+        _ => return Advice::Unknown,
+    };
+
+    // positive if the room needs warming up, negative if it needs cooling down
+    let needed_change = comfort_tenth_celsius - inside;
+    // positive if outside air warms up the room, negative if it cools it down
+    let offered_change = outside - inside;
+
+    // how much the outside air helps (in the direction we need)
+    let helpful_change = if needed_change >= 0 { offered_change } else { -offered_change };
+
+    let (min_needed, min_helpful) = if previous == Advice::OpenWindow {
+        // keep the window open until we reach the target
+        (1, 1)
+    } else {
+        (hysteresis_tenth_celsius + 1, hysteresis_tenth_celsius.max(1))
+    };
+
+    if needed_change.abs() >= min_needed && helpful_change >= min_helpful {
+        Advice::OpenWindow
+    } else {
+        Advice::KeepClosed
+    }
+}
@@ -0,0 +1,167 @@
+//! A software wall clock.
+//!
+//! The clock is set from date and time telegrams (e.g. those of a weather station) and kept
+//! running in between using the SysTick counter.
+
+
+use core::fmt;
+
+
+/// A calendar date.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Date {
+    pub year: u16,
+
+    /// The month, 1 (January) to 12 (December).
+    pub month: u8,
+
+    /// The day of the month, starting at 1.
+    pub day: u8,
+}
+impl Date {
+    /// Returns the date if it is valid.
+    pub fn new(year: u16, month: u8, day: u8) -> Option<Self> {
+        if !(1..=12).contains(&month) {
+            return None;
+        }
+        if !(1..=days_in_month(year, month)).contains(&day) {
+            return None;
+        }
+        Some(Self { year, month, day })
+    }
+
+    /// Returns the date of the following day.
+    pub fn next_day(&self) -> Self {
+        if self.day < days_in_month(self.year, self.month) {
+            Self { day: self.day + 1, ..*self }
+        } else if self.month < 12 {
+            Self { month: self.month + 1, day: 1, ..*self }
+        } else {
+            Self { year: self.year + 1, month: 1, day: 1 }
+        }
+    }
+}
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+
+/// A time of day in 24-hour format.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct TimeOfDay {
+    /// The day of the week, 1 (Monday) to 7 (Sunday).
+    pub weekday: u8,
+
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+impl TimeOfDay {
+    /// Returns the time if it is valid.
+    pub fn new(weekday: u8, hour: u8, minute: u8, second: u8) -> Option<Self> {
+        if !(1..=7).contains(&weekday) || hour > 23 || minute > 59 || second > 59 {
+            return None;
+        }
+        Some(Self { weekday, hour, minute, second })
+    }
+
+    /// Advances the time by one second. Returns whether midnight has been passed.
+    fn tick(&mut self) -> bool {
+        self.second += 1;
+        if self.second < 60 {
+            return false;
+        }
+        self.second = 0;
+        self.minute += 1;
+        if self.minute < 60 {
+            return false;
+        }
+        self.minute = 0;
+        self.hour += 1;
+        if self.hour < 24 {
+            return false;
+        }
+        self.hour = 0;
+        self.weekday = (self.weekday % 7) + 1;
+        true
+    }
+}
+impl fmt::Display for TimeOfDay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+        write!(
+            f, "{} {:02}:{:02}:{:02}",
+            WEEKDAYS[usize::from(self.weekday - 1)], self.hour, self.minute, self.second,
+        )
+    }
+}
+
+
+const fn is_leap_year(year: u16) -> bool {
+    (year.is_multiple_of(4) && !year.is_multiple_of(100)) || year.is_multiple_of(400)
+}
+
+const fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 => if is_leap_year(year) { 29 } else { 28 },
+        4|6|9|11 => 30,
+        _ => 31,
+    }
+}
+
+
+pub struct WallClock {
+    date: Option<Date>,
+    time: Option<TimeOfDay>,
+
+    /// The value of the SysTick counter up to which the clock has been advanced.
+    last_counter: u32,
+
+    /// Milliseconds that have passed but not yet been added to the time.
+    pending_millis: u32,
+}
+impl WallClock {
+    pub const fn new() -> Self {
+        Self {
+            date: None,
+            time: None,
+            last_counter: 0,
+            pending_millis: 0,
+        }
+    }
+
+    pub fn date(&self) -> Option<Date> { self.date }
+    pub fn time(&self) -> Option<TimeOfDay> { self.time }
+
+    pub fn set_date(&mut self, date: Date) {
+        self.date = Some(date);
+    }
+
+    /// Sets the time; `counter` is the current value of the SysTick counter.
+    pub fn set_time(&mut self, time: TimeOfDay, counter: u32) {
+        self.time = Some(time);
+        self.last_counter = counter;
+        self.pending_millis = 0;
+    }
+
+    /// Advances the clock to the current value of the SysTick counter.
+    ///
+    /// Must be called more often than the SysTick counter wraps around (every 49 days).
+    pub fn advance(&mut self, counter: u32) {
+        let elapsed = counter.wrapping_sub(self.last_counter);
+        self.last_counter = counter;
+
+        let Some(time) = self.time.as_mut() else {
+            return;
+        };
+        self.pending_millis += elapsed;
+        while self.pending_millis >= 1000 {
+            self.pending_millis -= 1000;
+            if time.tick() {
+                self.date = self.date.map(|date| date.next_day());
+            }
+        }
+    }
+}
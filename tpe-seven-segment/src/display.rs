@@ -0,0 +1,238 @@
+//! The contents of a three-digit seven-segment display.
+
+
+use crate::{glyph, SegmentCombo};
+
+
+/// A 12-bit brightness value.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Brightness {
+    inner: u16,
+}
+impl Brightness {
+    pub const fn new(inner: u16) -> Option<Self> {
+        if inner <= 0x0FFF {
+            Some(Self { inner })
+        } else {
+            None
+        }
+    }
+
+    pub const fn as_u16(&self) -> u16 { self.inner }
+}
+impl TryFrom<u16> for Brightness {
+    type Error = ();
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        Self::new(value).ok_or(())
+    }
+}
+impl From<Brightness> for u16 {
+    fn from(value: Brightness) -> Self { value.as_u16() }
+}
+
+
+pub struct TempDisplayState {
+    lit_segments: [SegmentCombo; 3],
+    brightness: Brightness,
+    reversed_order: bool,
+    dirty: bool,
+
+    /// The intensity of each segment relative to the brightness, in percent (indexed by the bit of
+    /// the segment in [`SegmentCombo`]).
+    intensities_percent: [[u8; 8]; 3],
+
+    /// How long it takes to cross-fade a digit from its previous to its new segments, in
+    /// milliseconds; 0 to switch immediately.
+    fade_millis: u32,
+
+    /// The segments each digit is fading out from.
+    previous_segments: [SegmentCombo; 3],
+
+    /// The value of the SysTick counter at which each fade started, if it has started.
+    fade_started_at: [Option<u32>; 3],
+
+    /// How far each fade has progressed, in permille.
+    fade_progress_permille: [u16; 3],
+}
+impl TempDisplayState {
+    pub fn new(reversed_order: bool) -> Self {
+        Self {
+            lit_segments: [SegmentCombo::empty(); 3],
+            brightness: Brightness::new(1).unwrap(),
+            reversed_order,
+            dirty: false,
+            intensities_percent: [[100; 8]; 3],
+            fade_millis: 0,
+            previous_segments: [SegmentCombo::empty(); 3],
+            fade_started_at: [None; 3],
+            fade_progress_permille: [1000; 3],
+        }
+    }
+
+    /// Returns the 12-bit PWM value of a single segment, taking into account the brightness, the
+    /// intensity of the segment and a cross-fade in progress.
+    ///
+    /// Unlike the other methods, this takes the position of the digit in the order in which the
+    /// digits are wired up, like [`lit_segments`](Self::lit_segments) returns them.
+    pub fn segment_level(&self, real_position: usize, segment: SegmentCombo) -> u16 {
+        assert!(real_position < 3);
+        assert_eq!(segment.bits().count_ones(), 1, "exactly one segment must be given");
+        let element_index = segment.bits().trailing_zeros() as usize;
+        let was_lit = self.previous_segments[real_position].contains(segment);
+        let is_lit = self.lit_segments[real_position].contains(segment);
+        let progress = u32::from(self.fade_progress_permille[real_position]);
+        let fade_permille = match (was_lit, is_lit) {
+            (false, false) => return 0,
+            (true, true) => 1000,
+            (false, true) => progress,
+            (true, false) => 1000 - progress,
+        };
+        let level = u32::from(self.brightness.as_u16())
+            * u32::from(self.intensities_percent[real_position][element_index])
+            * fade_permille
+            / (100 * 1000);
+        if level == 0 && fade_permille == 1000 && self.intensities_percent[real_position][element_index] > 0 {
+            // don't let a dimmed segment disappear at the lowest brightness
+            1
+        } else {
+            level as u16
+        }
+    }
+
+    /// Returns the segments lit on each digit, in the order in which the digits are wired up.
+    pub fn lit_segments(&self) -> [SegmentCombo; 3] { self.lit_segments }
+
+    pub fn brightness(&self) -> Brightness { self.brightness }
+
+    pub fn is_dirty(&self) -> bool { self.dirty }
+
+    /// Notes that the display now shows the stored contents.
+    pub fn mark_clean(&mut self) {
+        self.dirty = false;
+    }
+
+    pub fn set_brightness(&mut self, brightness: Brightness) {
+        if self.brightness != brightness {
+            self.brightness = brightness;
+            self.dirty = true;
+        }
+    }
+
+    /// Sets the intensity of the given segments of a digit relative to the brightness, in percent.
+    ///
+    /// This allows e.g. dimming the decimal point or emphasizing a digit.
+    pub fn set_intensity(&mut self, position: usize, segments: SegmentCombo, intensity_percent: u8) {
+        assert!(position < 3);
+        let real_position = if self.reversed_order { 2 - position } else { position };
+        let intensity_percent = intensity_percent.min(100);
+        for (element_index, intensity) in self.intensities_percent[real_position].iter_mut().enumerate() {
+            let element = SegmentCombo::from_bits_retain(1 << element_index);
+            if segments.contains(element) && *intensity != intensity_percent {
+                *intensity = intensity_percent;
+                self.dirty = true;
+            }
+        }
+    }
+
+    /// Sets how long a digit takes to cross-fade to new segments, in milliseconds.
+    ///
+    /// If this is not 0, [`advance_fades`](Self::advance_fades) must be called regularly.
+    pub fn set_fade_millis(&mut self, fade_millis: u32) {
+        self.fade_millis = fade_millis;
+    }
+
+    /// Advances the cross-fades in progress; `counter` is the current value of the SysTick counter.
+    pub fn advance_fades(&mut self, counter: u32) {
+        for real_position in 0..self.lit_segments.len() {
+            if self.fade_progress_permille[real_position] >= 1000 {
+                continue;
+            }
+
+            let started_at = *self.fade_started_at[real_position].get_or_insert(counter);
+            let elapsed = counter.wrapping_sub(started_at);
+            let progress = if elapsed >= self.fade_millis {
+                1000
+            } else {
+                (elapsed * 1000 / self.fade_millis) as u16
+            };
+            if self.fade_progress_permille[real_position] != progress {
+                self.fade_progress_permille[real_position] = progress;
+                self.dirty = true;
+            }
+            if progress >= 1000 {
+                self.previous_segments[real_position] = self.lit_segments[real_position];
+                self.fade_started_at[real_position] = None;
+            }
+        }
+    }
+
+    pub fn set_segments(&mut self, position: usize, segments: SegmentCombo) {
+        assert!(position < 3);
+        let real_position = if self.reversed_order { 2 - position } else { position };
+        if self.lit_segments[real_position] != segments {
+            if self.fade_millis == 0 {
+                self.previous_segments[real_position] = segments;
+                self.fade_progress_permille[real_position] = 1000;
+            } else {
+                // fade from whatever we were fading to
+                self.previous_segments[real_position] = self.lit_segments[real_position];
+                self.fade_progress_permille[real_position] = 0;
+                self.fade_started_at[real_position] = None;
+            }
+            self.lit_segments[real_position] = segments;
+            self.dirty = true;
+        }
+    }
+
+    pub fn set_digit(&mut self, position: usize, ascii_digit: u8, decimal_point: bool) {
+        assert!(position < 3);
+        let character_segments = glyph(char::from(ascii_digit))
+            .unwrap_or(SegmentCombo::empty());
+        let decimal_point_segment = if decimal_point {
+            SegmentCombo::DECIMAL_POINT
+        } else {
+            SegmentCombo::empty()
+        };
+        self.set_segments(position, character_segments | decimal_point_segment);
+    }
+
+    /// Shows the text, left-aligned, on the display.
+    ///
+    /// A period lights up the decimal point of the preceding character. Characters beyond the
+    /// third are cut off; characters that cannot be shown are left blank.
+    pub fn set_text(&mut self, text: &str) {
+        let mut segments = [SegmentCombo::empty(); 3];
+        let mut position = 0;
+        for character in text.chars() {
+            if character == '.' && position > 0 && !segments[position - 1].contains(SegmentCombo::DECIMAL_POINT) {
+                segments[position - 1] |= SegmentCombo::DECIMAL_POINT;
+                continue;
+            }
+            if position == segments.len() {
+                break;
+            }
+            segments[position] = if character == '.' {
+                SegmentCombo::DECIMAL_POINT
+            } else {
+                glyph(character).unwrap_or(SegmentCombo::empty())
+            };
+            position += 1;
+        }
+        for (position, position_segments) in segments.into_iter().enumerate() {
+            self.set_segments(position, position_segments);
+        }
+    }
+
+    pub fn set_nibble_digit(&mut self, position: usize, nibble: u8, decimal_point: bool) {
+        assert!(position < 3);
+        let ascii_digit = if nibble >= 0x10 {
+            b' '
+        } else {
+            [
+                b'0', b'1', b'2', b'3', b'4', b'5', b'6', b'7',
+                b'8', b'9', b'A', b'b', b'C', b'd', b'E', b'F',
+            ][usize::from(nibble)]
+        };
+        self.set_digit(position, ascii_digit, decimal_point);
+    }
+}
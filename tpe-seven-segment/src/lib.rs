@@ -0,0 +1,213 @@
+#![cfg_attr(not(test), no_std)]
+
+
+mod display;
+mod transport;
+
+
+use bitflags::bitflags;
+
+pub use crate::display::{Brightness, TempDisplayState};
+pub use crate::transport::{RecordingDisplay, SevenSegmentDisplay};
+
+
+bitflags! {
+    #[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+    pub struct SegmentCombo : u8 {
+        const DECIMAL_POINT = 0b0000_0001;
+        const MIDDLE = 0b0000_0010;
+        const TOP_LEFT = 0b0000_0100;
+        const BOTTOM_LEFT = 0b0000_1000;
+        const BOTTOM = 0b0001_0000;
+        const BOTTOM_RIGHT = 0b0010_0000;
+        const TOP_RIGHT = 0b0100_0000;
+        const TOP = 0b1000_0000;
+    }
+}
+
+
+const SUPPORTED_CHARACTERS_SORTED: [char; 50] = [
+    ' ', '-', '0', '1',
+    '2', '3', '4', '5',
+    '6', '7', '8', '9',
+    '=', '?', 'A', 'B',
+    'C', 'D', 'E', 'F',
+    'G', 'H', 'I', 'J',
+    'L', 'N', 'O', 'P',
+    'S', 'U', 'Y', '_',
+    'a', 'b', 'c', 'd',
+    'e', 'f', 'g', 'h',
+    'i', 'j', 'n', 'o',
+    'q', 'r', 't', 'u',
+    'y', '°',
+];
+// same order as SUPPORTED_CHARACTERS_SORTED
+const CHARACTER_SEGMENTS: [SegmentCombo; 50] = {
+    const M: u8 = SegmentCombo::MIDDLE.bits();
+    const T: u8 = SegmentCombo::TOP.bits();
+    const TL: u8 = SegmentCombo::TOP_LEFT.bits();
+    const BL: u8 = SegmentCombo::BOTTOM_LEFT.bits();
+    const B: u8 = SegmentCombo::BOTTOM.bits();
+    const BR: u8 = SegmentCombo::BOTTOM_RIGHT.bits();
+    const TR: u8 = SegmentCombo::TOP_RIGHT.bits();
+
+    [
+        SegmentCombo::from_bits_retain(0), // space
+        SegmentCombo::from_bits_retain(M), // -
+        SegmentCombo::from_bits_retain(T | TL | BL | B | BR | TR), // 0
+        SegmentCombo::from_bits_retain(TR | BR), // 1
+        SegmentCombo::from_bits_retain(T | TR | M | BL | B), // 2
+        SegmentCombo::from_bits_retain(T | TR | M | BR | B), // 3
+        SegmentCombo::from_bits_retain(TL | M | TR | BR), // 4
+        SegmentCombo::from_bits_retain(T | TL | M | BR | B), // 5
+        SegmentCombo::from_bits_retain(T | TL | BL | B | BR | M), // 6
+        SegmentCombo::from_bits_retain(TL | T | TR | BR), // 7
+        SegmentCombo::from_bits_retain(T | TL | TR | M | BL | BR | B), // 8
+        SegmentCombo::from_bits_retain(T | TL | TR | M | BR | B), // 9
+        SegmentCombo::from_bits_retain(M | B), // =
+        SegmentCombo::from_bits_retain(T | TR | M | BL), // ?
+        SegmentCombo::from_bits_retain(BL | TL | T | TR | BR | M), // A
+        SegmentCombo::from_bits_retain(TL | BL | B | BR | M), // b
+        SegmentCombo::from_bits_retain(T | TL | BL | B), // C
+        SegmentCombo::from_bits_retain(TR | BL | B | BR | M), // d
+        SegmentCombo::from_bits_retain(T | TL | M | BL | B), // E
+        SegmentCombo::from_bits_retain(T | TL | M | BL), // F
+        SegmentCombo::from_bits_retain(T | TL | BL | B | BR), // G
+        SegmentCombo::from_bits_retain(TL | BL | M | TR | BR), // H
+        SegmentCombo::from_bits_retain(TL | BL), // I
+        SegmentCombo::from_bits_retain(TR | BR | B | BL), // J
+        SegmentCombo::from_bits_retain(TL | BL | B), // L
+        SegmentCombo::from_bits_retain(BL | TL | T | TR | BR), // N
+        SegmentCombo::from_bits_retain(T | TL | BL | B | BR | TR), // O
+        SegmentCombo::from_bits_retain(T | TL | TR | M | BL), // P
+        SegmentCombo::from_bits_retain(T | TL | M | BR | B), // S
+        SegmentCombo::from_bits_retain(TL | BL | B | BR | TR), // U
+        SegmentCombo::from_bits_retain(TL | TR | M | BR | B), // Y
+        SegmentCombo::from_bits_retain(B), // _
+        SegmentCombo::from_bits_retain(T | TR | M | BL | BR | B), // a
+        SegmentCombo::from_bits_retain(TL | BL | B | BR | M), // b
+        SegmentCombo::from_bits_retain(M | BL | B), // c
+        SegmentCombo::from_bits_retain(TR | BL | B | BR | M), // d
+        SegmentCombo::from_bits_retain(T | TL | TR | M | BL | B), // e
+        SegmentCombo::from_bits_retain(T | TL | M | BL), // f
+        SegmentCombo::from_bits_retain(T | TL | TR | M | BR | B), // g
+        SegmentCombo::from_bits_retain(TL | BL | M | BR), // h
+        SegmentCombo::from_bits_retain(BL), // i
+        SegmentCombo::from_bits_retain(BR | B), // j
+        SegmentCombo::from_bits_retain(BL | M | BR), // n
+        SegmentCombo::from_bits_retain(M | BL | B | BR), // o
+        SegmentCombo::from_bits_retain(T | TL | TR | M | BR), // q
+        SegmentCombo::from_bits_retain(BL | M), // r
+        SegmentCombo::from_bits_retain(TL | BL | B | M), // t
+        SegmentCombo::from_bits_retain(BL | B | BR), // u
+        SegmentCombo::from_bits_retain(TL | TR | M | BR | B), // y
+        SegmentCombo::from_bits_retain(T | TL | TR | M), // °
+    ]
+};
+
+
+/// Returns the segments to light up to show the given character.
+///
+/// Letters which only have a glyph in the other case are shown in that case. Returns `None` if the
+/// character cannot be shown.
+pub fn glyph(character: char) -> Option<SegmentCombo> {
+    let index = SUPPORTED_CHARACTERS_SORTED.binary_search(&character)
+        .or_else(|_| SUPPORTED_CHARACTERS_SORTED.binary_search(&character.to_ascii_uppercase()))
+        .or_else(|_| SUPPORTED_CHARACTERS_SORTED.binary_search(&character.to_ascii_lowercase()))
+        .ok()?;
+    Some(CHARACTER_SEGMENTS[index])
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{glyph, SegmentCombo, SUPPORTED_CHARACTERS_SORTED};
+
+    #[test]
+    fn test_sorted() {
+        for pair in SUPPORTED_CHARACTERS_SORTED.windows(2) {
+            assert!(pair[0] < pair[1], "{:?} is not sorted before {:?}", pair[0], pair[1]);
+        }
+    }
+
+    #[test]
+    fn test_glyph_bits() {
+        // bits: top, top right, bottom right, bottom, bottom left, top left, middle, decimal point
+        const EXPECTED: [(char, u8); 50] = [
+            (' ', 0b0000_0000),
+            ('-', 0b0000_0010),
+            ('0', 0b1111_1100),
+            ('1', 0b0110_0000),
+            ('2', 0b1101_1010),
+            ('3', 0b1111_0010),
+            ('4', 0b0110_0110),
+            ('5', 0b1011_0110),
+            ('6', 0b1011_1110),
+            ('7', 0b1110_0100),
+            ('8', 0b1111_1110),
+            ('9', 0b1111_0110),
+            ('=', 0b0001_0010),
+            ('?', 0b1100_1010),
+            ('A', 0b1110_1110),
+            ('B', 0b0011_1110),
+            ('C', 0b1001_1100),
+            ('D', 0b0111_1010),
+            ('E', 0b1001_1110),
+            ('F', 0b1000_1110),
+            ('G', 0b1011_1100),
+            ('H', 0b0110_1110),
+            ('I', 0b0000_1100),
+            ('J', 0b0111_1000),
+            ('L', 0b0001_1100),
+            ('N', 0b1110_1100),
+            ('O', 0b1111_1100),
+            ('P', 0b1100_1110),
+            ('S', 0b1011_0110),
+            ('U', 0b0111_1100),
+            ('Y', 0b0111_0110),
+            ('_', 0b0001_0000),
+            ('a', 0b1111_1010),
+            ('b', 0b0011_1110),
+            ('c', 0b0001_1010),
+            ('d', 0b0111_1010),
+            ('e', 0b1101_1110),
+            ('f', 0b1000_1110),
+            ('g', 0b1111_0110),
+            ('h', 0b0010_1110),
+            ('i', 0b0000_1000),
+            ('j', 0b0011_0000),
+            ('n', 0b0010_1010),
+            ('o', 0b0011_1010),
+            ('q', 0b1110_0110),
+            ('r', 0b0000_1010),
+            ('t', 0b0001_1110),
+            ('u', 0b0011_1000),
+            ('y', 0b0111_0110),
+            ('°', 0b1100_0110),
+        ];
+        for (character, bits) in EXPECTED {
+            assert_eq!(
+                glyph(character), Some(SegmentCombo::from_bits_retain(bits)),
+                "wrong glyph for {:?}", character,
+            );
+        }
+        assert_eq!(EXPECTED.len(), SUPPORTED_CHARACTERS_SORTED.len());
+    }
+
+    #[test]
+    fn test_glyph_other_case() {
+        assert_eq!(glyph('l'), glyph('L'));
+        assert_eq!(glyph('p'), glyph('P'));
+        assert_eq!(glyph('R'), glyph('r'));
+        assert_eq!(glyph('T'), glyph('t'));
+    }
+
+    #[test]
+    fn test_glyph_unsupported() {
+        assert_eq!(glyph('K'), None);
+        assert_eq!(glyph('m'), None);
+        assert_eq!(glyph('W'), None);
+        assert_eq!(glyph('\n'), None);
+        assert_eq!(glyph('€'), None);
+    }
+}
@@ -0,0 +1,123 @@
+//! Getting the contents of a display onto the hardware.
+//!
+//! A [`TempDisplayState`] only describes what a display should show; an implementation of
+//! [`SevenSegmentDisplay`] knows how to get it there, whether through a TLC5947 behind an I2C-SPI
+//! bridge, a TLC5947 on a local SPI bus or a different driver chip such as a MAX7219 or HT16K33.
+
+
+use crate::SegmentCombo;
+use crate::display::{Brightness, TempDisplayState};
+
+
+/// A way of transmitting the contents of a display to the hardware.
+pub trait SevenSegmentDisplay {
+    /// Whatever the transport needs to access the hardware, e.g. the microcontroller's
+    /// peripherals.
+    type Context: ?Sized;
+
+    /// Transmits the contents to the display, whether or not they have changed.
+    fn transmit(&mut self, context: &Self::Context, content: &TempDisplayState);
+
+    /// Transmits the contents to the display if they have changed since the last transmission or
+    /// if `force` is set, then marks them as clean.
+    fn update(&mut self, context: &Self::Context, content: &mut TempDisplayState, force: bool) {
+        if force || content.is_dirty() {
+            self.transmit(context, content);
+            content.mark_clean();
+        }
+    }
+}
+
+
+/// A display that remembers what was transmitted to it instead of showing it, for tests.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct RecordingDisplay {
+    /// How many times the contents have been transmitted.
+    pub transmissions: usize,
+
+    /// The segments lit on each digit as of the last transmission, in wiring order.
+    pub lit_segments: [SegmentCombo; 3],
+
+    /// The brightness as of the last transmission.
+    pub brightness: Brightness,
+}
+impl RecordingDisplay {
+    pub const fn new() -> Self {
+        Self {
+            transmissions: 0,
+            lit_segments: [SegmentCombo::empty(); 3],
+            brightness: Brightness::new(0).unwrap(),
+        }
+    }
+}
+impl SevenSegmentDisplay for RecordingDisplay {
+    type Context = ();
+
+    fn transmit(&mut self, _context: &Self::Context, content: &TempDisplayState) {
+        self.transmissions += 1;
+        self.lit_segments = content.lit_segments();
+        self.brightness = content.brightness();
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{RecordingDisplay, SevenSegmentDisplay};
+    use crate::{glyph, SegmentCombo};
+    use crate::display::{Brightness, TempDisplayState};
+
+    #[test]
+    fn test_update_only_when_dirty() {
+        let mut content = TempDisplayState::new(false);
+        let mut display = RecordingDisplay::new();
+
+        display.update(&(), &mut content, false);
+        assert_eq!(display.transmissions, 0);
+
+        content.set_text("12");
+        display.update(&(), &mut content, false);
+        assert_eq!(display.transmissions, 1);
+        assert!(!content.is_dirty());
+        assert_eq!(display.lit_segments, [glyph('1').unwrap(), glyph('2').unwrap(), SegmentCombo::empty()]);
+
+        // same text again
+        content.set_text("12");
+        display.update(&(), &mut content, false);
+        assert_eq!(display.transmissions, 1);
+
+        display.update(&(), &mut content, true);
+        assert_eq!(display.transmissions, 2);
+    }
+
+    #[test]
+    fn test_reversed_order() {
+        let mut content = TempDisplayState::new(true);
+        let mut display = RecordingDisplay::new();
+
+        content.set_text("1.2");
+        content.set_brightness(Brightness::new(0x123).unwrap());
+        display.update(&(), &mut content, false);
+        assert_eq!(
+            display.lit_segments,
+            [SegmentCombo::empty(), glyph('2').unwrap(), glyph('1').unwrap() | SegmentCombo::DECIMAL_POINT],
+        );
+        assert_eq!(display.brightness, Brightness::new(0x123).unwrap());
+    }
+
+    #[test]
+    fn test_segment_levels() {
+        let mut content = TempDisplayState::new(false);
+        content.set_brightness(Brightness::new(0xABC).unwrap());
+        content.set_segments(0, SegmentCombo::DECIMAL_POINT | SegmentCombo::MIDDLE);
+        content.set_segments(2, SegmentCombo::TOP);
+        content.set_intensity(2, SegmentCombo::TOP, 50);
+
+        assert_eq!(content.segment_level(0, SegmentCombo::DECIMAL_POINT), 0xABC);
+        assert_eq!(content.segment_level(0, SegmentCombo::MIDDLE), 0xABC);
+        assert_eq!(content.segment_level(0, SegmentCombo::TOP), 0);
+        assert_eq!(content.segment_level(1, SegmentCombo::MIDDLE), 0);
+        assert_eq!(content.segment_level(2, SegmentCombo::TOP_RIGHT), 0);
+        assert_eq!(content.segment_level(2, SegmentCombo::TOP), 0xABC / 2);
+    }
+}